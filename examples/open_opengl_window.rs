@@ -6,7 +6,7 @@ use glow::*;
 
 #[cfg(target_os = "macos")]
 use baseview::copy_to_clipboard;
-use baseview::{Event, EventStatus, Window, WindowHandler, WindowScalePolicy};
+use baseview::{Event, EventStatus, TouchPolicy, Window, WindowHandler, WindowScalePolicy};
 
 #[derive(Debug, Clone)]
 enum Message {
@@ -149,6 +149,9 @@ impl WindowHandler for OpenWindowExample {
             }
             Event::Keyboard(e) => println!("Keyboard event: {:?}", e),
             Event::Window(e) => println!("Window event: {:?}", e),
+            Event::Touch(e) => println!("Touch event: {:?}", e),
+            Event::Pen(e) => println!("Pen event: {:?}", e),
+            Event::Timer(id) => println!("Timer event: {}", id),
         }
 
         EventStatus::Captured
@@ -160,6 +163,8 @@ fn main() {
         title: "baseview".into(),
         size: baseview::Size::new(512.0, 512.0),
         scale: WindowScalePolicy::SystemScaleFactor,
+        transparent: false,
+        touch: TouchPolicy::MouseOnly,
         #[cfg(feature = "opengl")]
         gl_config: Some(Default::default()),
         #[cfg(feature = "vulkan")]
@@ -4,7 +4,9 @@ use rtrb::{Consumer, RingBuffer};
 
 #[cfg(target_os = "macos")]
 use baseview::copy_to_clipboard;
-use baseview::{Event, EventStatus, MouseEvent, Window, WindowHandler, WindowScalePolicy};
+use baseview::{
+    Event, EventStatus, MouseEvent, TouchPolicy, Window, WindowHandler, WindowScalePolicy,
+};
 
 #[derive(Debug, Clone)]
 enum Message {
@@ -37,6 +39,9 @@ impl WindowHandler for OpenWindowExample {
             }
             Event::Keyboard(e) => println!("Keyboard event: {:?}", e),
             Event::Window(e) => println!("Window event: {:?}", e),
+            Event::Touch(e) => println!("Touch event: {:?}", e),
+            Event::Pen(e) => println!("Pen event: {:?}", e),
+            Event::Timer(id) => println!("Timer event: {}", id),
         }
 
         EventStatus::Captured
@@ -48,6 +53,8 @@ fn main() {
         title: "baseview".into(),
         size: baseview::Size::new(512.0, 512.0),
         scale: WindowScalePolicy::SystemScaleFactor,
+        transparent: false,
+        touch: TouchPolicy::TouchAndMouse,
         #[cfg(feature = "opengl")]
         gl_config: None,
         #[cfg(feature = "vulkan")]
@@ -2,7 +2,7 @@ use std::path::PathBuf;
 
 use keyboard_types::{KeyboardEvent, Modifiers};
 
-use crate::{Point, WindowInfo};
+use crate::{PhyPoint, Point, WindowInfo};
 
 #[derive(Debug, Copy, Clone, Eq, PartialEq)]
 pub enum MouseButton {
@@ -44,6 +44,13 @@ pub enum MouseEvent {
         modifiers: Modifiers,
     },
 
+    /// The mouse was moved while the cursor is locked with [crate::CursorGrabMode::Locked].
+    RawMotion {
+        /// The relative movement in logical pixels. Pointer acceleration is not applied if the
+        /// platform supports it.
+        delta: Point,
+    },
+
     /// A mouse button was pressed.
     ButtonPressed {
         /// The button that was pressed.
@@ -78,6 +85,10 @@ pub enum MouseEvent {
     /// May not be available on all platforms.
     CursorLeft,
 
+    /// The mouse capture from [crate::Window::capture_mouse] ended without calling
+    /// [crate::Window::release_mouse], for instance because another application grabbed the mouse.
+    CaptureLost,
+
     DragEntered {
         /// The logical coordinates of the mouse position
         position: Point,
@@ -111,9 +122,28 @@ pub enum MouseEvent {
 #[derive(Debug, Clone)]
 pub enum WindowEvent {
     Resized(WindowInfo),
+    /// The window's outer position changed, see [crate::Window::outer_position]. Currently only
+    /// sent on X11.
+    Moved(PhyPoint),
+    /// The window became visible, for instance because it or its parent window was mapped.
+    Shown,
+    /// The window is no longer visible, for instance because it was minimized or because the host
+    /// hid the parent window.
+    Hidden,
+    /// Whether the window is completely covered by other windows. Compositing managers draw windows
+    /// offscreen, so on X11 this will always be `false` while one is running.
+    Occluded(bool),
+    /// Whether the window has been maximized.
+    Maximized(bool),
+    /// Whether the window has been minimized.
+    Minimized(bool),
     Focused,
     Unfocused,
     WillClose,
+    /// Whether a compositing manager is running, and thus whether transparent windows are actually
+    /// drawn with transparency. On X11 this is sent once when the window is opened, and again
+    /// whenever a compositing manager is started or stopped.
+    CompositorChanged(bool),
 }
 
 #[derive(Debug, Clone)]
@@ -121,6 +151,65 @@ pub enum Event {
     Mouse(MouseEvent),
     Keyboard(KeyboardEvent),
     Window(WindowEvent),
+    Touch(TouchEvent),
+    /// Sent in addition to the [MouseEvent]s when a pen on a graphics tablet moves.
+    Pen(PenEvent),
+    /// A timer set with `Window::set_timer` fired. Contains the timer's ID. Timers are currently
+    /// only supported on Linux.
+    Timer(u32),
+}
+
+/// The stage of a touch's lifetime a [TouchEvent] describes.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum TouchPhase {
+    /// A finger touched the screen.
+    Started,
+    /// The finger moved, or its pressure changed.
+    Moved,
+    /// The finger was lifted from the screen.
+    Ended,
+    /// The touch was interrupted, for instance because the window was hidden. No further events
+    /// are sent for the touch.
+    Cancelled,
+}
+
+/// A touch screen event, sent when the window was opened with a [crate::TouchPolicy] that enables
+/// touch events.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TouchEvent {
+    /// Identifies the touch. The ID stays the same from [TouchPhase::Started] until
+    /// [TouchPhase::Ended] or [TouchPhase::Cancelled], but may be reused by later touches.
+    pub id: u64,
+    pub phase: TouchPhase,
+    /// The logical coordinates of the touch
+    pub position: Point,
+    /// The touch's pressure in the `[0, 1]` range, if the touch screen reports it.
+    pub force: Option<f64>,
+}
+
+/// The state of a pen on a graphics tablet. Currently only supported on X11.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PenEvent {
+    /// The logical coordinates of the pen
+    pub position: Point,
+    /// The pen's pressure in the `[0, 1]` range
+    pub pressure: f64,
+    /// The pen's tilt along the x axis in the `[-1, 1]` range, where positive values mean the
+    /// pen is tilted to the right. Zero if the pen does not report tilt.
+    pub tilt_x: f64,
+    /// The pen's tilt along the y axis in the `[-1, 1]` range, where positive values mean the
+    /// pen is tilted towards the user. Zero if the pen does not report tilt.
+    pub tilt_y: f64,
+    /// The pen's rotation around its own axis in the `[0, 1]` range, for pens that support it
+    pub rotation: Option<f64>,
+    /// Whether the eraser end of the pen is being used
+    pub is_eraser: bool,
+    /// Whether the pen's tip touches the tablet
+    pub in_contact: bool,
+    /// Whether the pen's first and second barrel buttons are pressed
+    pub barrel_buttons: [bool; 2],
+    /// The modifiers that were held down just before the event.
+    pub modifiers: Modifiers,
 }
 
 #[derive(Debug, Clone, Copy, PartialEq)]
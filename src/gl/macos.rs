@@ -25,6 +25,7 @@ pub type CreationFailedError = ();
 pub struct GlContext {
     view: id,
     context: id,
+    config: GlConfig,
 }
 
 impl GlContext {
@@ -103,9 +104,32 @@ impl GlContext {
             NSOpenGLContextParameter::NSOpenGLCPSwapInterval,
         );
 
+        // `NSOpenGLPixelFormat` picks the closest match rather than an exact one, so report the
+        // attributes the pixel format actually ended up with
+        let color_size = pixel_format_value(pixel_format, NSOpenGLPFAColorSize as u32);
+        // The color size covers the RGB channels and may include padding, so it's either split
+        // across three or four channels
+        let channel_bits = if color_size % 3 == 0 { color_size / 3 } else { color_size / 4 };
+        let sample_buffers = pixel_format_value(pixel_format, NSOpenGLPFASampleBuffers as u32);
+        let config = GlConfig {
+            red_bits: channel_bits as u8,
+            green_bits: channel_bits as u8,
+            blue_bits: channel_bits as u8,
+            alpha_bits: pixel_format_value(pixel_format, NSOpenGLPFAAlphaSize as u32) as u8,
+            depth_bits: pixel_format_value(pixel_format, NSOpenGLPFADepthSize as u32) as u8,
+            stencil_bits: pixel_format_value(pixel_format, NSOpenGLPFAStencilSize as u32) as u8,
+            samples: if sample_buffers > 0 {
+                Some(pixel_format_value(pixel_format, NSOpenGLPFASamples as u32) as u8)
+            } else {
+                None
+            },
+            double_buffer: pixel_format_value(pixel_format, NSOpenGLPFADoubleBuffer as u32) != 0,
+            ..config
+        };
+
         let () = msg_send![pixel_format, release];
 
-        Ok(GlContext { view, context })
+        Ok(GlContext { view, context, config })
     }
 
     pub unsafe fn make_current(&self) {
@@ -134,6 +158,23 @@ impl GlContext {
         }
     }
 
+    pub fn set_swap_interval(&self, interval: i32) -> Result<(), GlError> {
+        // `NSOpenGLCPSwapInterval` only knows about on and off, and there's no adaptive vsync
+        let interval = (interval != 0) as i32;
+        unsafe {
+            self.context.setValues_forParameter_(
+                &interval,
+                NSOpenGLContextParameter::NSOpenGLCPSwapInterval,
+            );
+        }
+
+        Ok(())
+    }
+
+    pub fn config(&self) -> GlConfig {
+        self.config.clone()
+    }
+
     /// On macOS the `NSOpenGLView` needs to be resized separtely from our main view.
     pub(crate) fn resize(&self, size: NSSize) {
         unsafe { NSView::setFrameSize(self.view, size) };
@@ -143,6 +184,19 @@ impl GlContext {
     }
 }
 
+/// Query an attribute of the pixel format for the first virtual screen.
+unsafe fn pixel_format_value(pixel_format: id, attribute: u32) -> i32 {
+    let mut value: i32 = 0;
+    let () = msg_send![
+        pixel_format,
+        getValues: &mut value as *mut i32
+        forAttribute: attribute
+        forVirtualScreen: 0i32
+    ];
+
+    value
+}
+
 impl Drop for GlContext {
     fn drop(&mut self) {
         unsafe {
@@ -1,3 +1,4 @@
+use std::cell::Cell;
 use std::ffi::c_void;
 use std::marker::PhantomData;
 
@@ -35,6 +36,8 @@ pub struct GlConfig {
     pub srgb: bool,
     pub double_buffer: bool,
     pub vsync: bool,
+    pub selection: ConfigSelection,
+    pub api: GlApi,
 }
 
 impl Default for GlConfig {
@@ -52,6 +55,8 @@ impl Default for GlConfig {
             srgb: true,
             double_buffer: true,
             vsync: false,
+            selection: ConfigSelection::AtLeast,
+            api: GlApi::Glx,
         }
     }
 }
@@ -60,6 +65,30 @@ impl Default for GlConfig {
 pub enum Profile {
     Compatibility,
     Core,
+    /// OpenGL ES, in which case [GlConfig::version] refers to the OpenGL ES version. Not
+    /// supported on macOS.
+    Es,
+}
+
+/// The API used to create the OpenGL context. This only has an effect on Linux, other platforms
+/// always use their native API.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum GlApi {
+    Glx,
+    /// Create the context through EGL using `EGL_KHR_platform_x11`. `libEGL.so` is loaded at
+    /// runtime, so it's only needed when this is used.
+    Egl,
+}
+
+/// How a framebuffer configuration is picked out of all configurations that satisfy a [GlConfig].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ConfigSelection {
+    /// Use whatever configuration the platform prefers. All bit depths and the sample count are
+    /// treated as minimums, so the resulting framebuffer may have more of them than requested.
+    AtLeast,
+    /// Prefer the configuration whose attributes are closest to the requested ones. Currently only
+    /// has an effect on X11, other platforms behave as [ConfigSelection::AtLeast].
+    PreferExact,
 }
 
 #[derive(Debug)]
@@ -67,10 +96,14 @@ pub enum GlError {
     InvalidWindowHandle,
     VersionNotSupported,
     CreationFailed(platform::CreationFailedError),
+    /// The swap interval cannot be set because the needed swap control extension is missing
+    SwapIntervalNotSupported,
 }
 
 pub struct GlContext {
     context: platform::GlContext,
+    /// The vsync setting from the last successful [Self::set_swap_interval] call, if any.
+    vsync: Cell<Option<bool>>,
     phantom: PhantomData<*mut ()>,
 }
 
@@ -79,8 +112,11 @@ impl GlContext {
     pub(crate) unsafe fn create(
         parent: &RawWindowHandle, config: GlConfig,
     ) -> Result<GlContext, GlError> {
-        platform::GlContext::create(parent, config)
-            .map(|context| GlContext { context, phantom: PhantomData })
+        platform::GlContext::create(parent, config).map(|context| GlContext {
+            context,
+            vsync: Cell::new(None),
+            phantom: PhantomData,
+        })
     }
 
     /// The X11 version needs to be set up in a different way compared to the Windows and macOS
@@ -88,7 +124,7 @@ impl GlContext {
     /// baseview, and then this object can be passed to the user.
     #[cfg(target_os = "linux")]
     pub(crate) fn new(context: platform::GlContext) -> GlContext {
-        GlContext { context, phantom: PhantomData }
+        GlContext { context, vsync: Cell::new(None), phantom: PhantomData }
     }
 
     pub unsafe fn make_current(&self) {
@@ -107,6 +143,41 @@ impl GlContext {
         self.context.swap_buffers();
     }
 
+    /// Create an OpenGL context that isn't tied to a window. This always uses EGL with Mesa's
+    /// surfaceless platform regardless of [GlConfig::api] and doesn't need an X11 server, which
+    /// makes it useful for testing renderers headlessly with llvmpipe. There is no default
+    /// framebuffer, so rendering needs to happen to framebuffer objects and
+    /// [Self::swap_buffers] does nothing.
+    #[cfg(target_os = "linux")]
+    pub fn create_headless(config: GlConfig) -> Result<GlContext, GlError> {
+        unsafe { platform::GlContext::create_headless(config) }.map(GlContext::new)
+    }
+
+    /// Change the swap interval at runtime. `0` disables vsync, `1` waits for the next vertical
+    /// blank before swapping buffers, and larger values wait for that many vertical blanks. `-1`
+    /// enables adaptive vsync, which swaps immediately if the frame missed the vertical blank. If
+    /// adaptive vsync is not supported then this falls back to regular vsync. The initial swap
+    /// interval is set through [GlConfig::vsync].
+    pub fn set_swap_interval(&self, interval: i32) -> Result<(), GlError> {
+        self.context.set_swap_interval(interval)?;
+        self.vsync.set(Some(interval != 0));
+
+        Ok(())
+    }
+
+    /// The configuration of the framebuffer that was actually created. This may differ from the
+    /// requested [GlConfig], for instance when the platform hands out a multisampled or
+    /// sRGB-capable framebuffer that was not asked for, or when it cannot provide an alpha channel.
+    /// [GlConfig::vsync] reflects the last swap interval set with [Self::set_swap_interval].
+    pub fn config(&self) -> GlConfig {
+        let mut config = self.context.config();
+        if let Some(vsync) = self.vsync.get() {
+            config.vsync = vsync;
+        }
+
+        config
+    }
+
     /// On macOS the `NSOpenGLView` needs to be resized separtely from our main view.
     #[cfg(target_os = "macos")]
     pub(crate) fn resize(&self, size: cocoa::foundation::NSSize) {
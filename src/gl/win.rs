@@ -8,9 +8,10 @@ use winapi::shared::ntdef::WCHAR;
 use winapi::shared::windef::{HDC, HGLRC, HWND};
 use winapi::um::libloaderapi::{FreeLibrary, GetProcAddress, LoadLibraryA};
 use winapi::um::wingdi::{
-    wglCreateContext, wglDeleteContext, wglGetProcAddress, wglMakeCurrent, ChoosePixelFormat,
-    DescribePixelFormat, SetPixelFormat, SwapBuffers, PFD_DOUBLEBUFFER, PFD_DRAW_TO_WINDOW,
-    PFD_MAIN_PLANE, PFD_SUPPORT_OPENGL, PFD_TYPE_RGBA, PIXELFORMATDESCRIPTOR,
+    wglCreateContext, wglDeleteContext, wglGetCurrentContext, wglGetCurrentDC, wglGetProcAddress,
+    wglMakeCurrent, ChoosePixelFormat, DescribePixelFormat, SetPixelFormat, SwapBuffers,
+    PFD_DOUBLEBUFFER, PFD_DRAW_TO_WINDOW, PFD_MAIN_PLANE, PFD_SUPPORT_OPENGL, PFD_TYPE_RGBA,
+    PIXELFORMATDESCRIPTOR,
 };
 use winapi::um::winnt::IMAGE_DOS_HEADER;
 use winapi::um::winuser::{
@@ -31,10 +32,16 @@ const WGL_CONTEXT_PROFILE_MASK_ARB: i32 = 0x9126;
 const WGL_CONTEXT_CORE_PROFILE_BIT_ARB: i32 = 0x00000001;
 const WGL_CONTEXT_COMPATIBILITY_PROFILE_BIT_ARB: i32 = 0x00000002;
 
+// See https://www.khronos.org/registry/OpenGL/extensions/EXT/WGL_EXT_create_context_es2_profile.txt
+
+const WGL_CONTEXT_ES2_PROFILE_BIT_EXT: i32 = 0x00000004;
+
 // See https://www.khronos.org/registry/OpenGL/extensions/ARB/WGL_ARB_pixel_format.txt
 
 type WglChoosePixelFormatARB =
     extern "system" fn(HDC, *const i32, *const f32, u32, *mut i32, *mut u32) -> i32;
+type WglGetPixelFormatAttribivARB =
+    extern "system" fn(HDC, i32, i32, u32, *const i32, *mut i32) -> i32;
 
 const WGL_DRAW_TO_WINDOW_ARB: i32 = 0x2001;
 const WGL_ACCELERATION_ARB: i32 = 0x2003;
@@ -70,6 +77,8 @@ pub struct GlContext {
     hdc: HDC,
     hglrc: HGLRC,
     gl_library: HMODULE,
+    config: GlConfig,
+    swap_interval_ext: Option<WglSwapIntervalEXT>,
 }
 
 extern "C" {
@@ -177,6 +186,17 @@ impl GlContext {
             }
         };
 
+        #[allow(non_snake_case)]
+        let wglGetPixelFormatAttribivARB: Option<WglGetPixelFormatAttribivARB> = {
+            let symbol = CString::new("wglGetPixelFormatAttribivARB").unwrap();
+            let addr = wglGetProcAddress(symbol.as_ptr());
+            if !addr.is_null() {
+                Some(std::mem::transmute(addr))
+            } else {
+                None
+            }
+        };
+
         #[allow(non_snake_case)]
         let wglSwapIntervalEXT: Option<WglSwapIntervalEXT> = {
             let symbol = CString::new("wglSwapIntervalEXT").unwrap();
@@ -238,9 +258,44 @@ impl GlContext {
         );
         SetPixelFormat(hdc, pixel_format, &pfd);
 
+        // `wglChoosePixelFormatARB()` treats most attributes as minimums, so the pixel format we
+        // got may differ from the requested configuration
+        let mut config = GlConfig {
+            red_bits: pfd.cRedBits,
+            green_bits: pfd.cGreenBits,
+            blue_bits: pfd.cBlueBits,
+            alpha_bits: pfd.cAlphaBits,
+            depth_bits: pfd.cDepthBits,
+            stencil_bits: pfd.cStencilBits,
+            double_buffer: pfd.dwFlags & PFD_DOUBLEBUFFER != 0,
+            ..config
+        };
+        if let Some(get_attribs) = wglGetPixelFormatAttribivARB {
+            let get_attrib = |attrib: i32| {
+                let mut value = 0;
+                if get_attribs(hdc, pixel_format, 0, 1, &attrib, &mut value) != 0 {
+                    Some(value)
+                } else {
+                    None
+                }
+            };
+
+            if let Some(sample_buffers) = get_attrib(WGL_SAMPLE_BUFFERS_ARB) {
+                config.samples = match get_attrib(WGL_SAMPLES_ARB) {
+                    Some(samples) if sample_buffers > 0 => Some(samples as u8),
+                    _ => None,
+                };
+            }
+            // This attribute is only known if `WGL_ARB_framebuffer_sRGB` is supported
+            if let Some(srgb) = get_attrib(WGL_FRAMEBUFFER_SRGB_CAPABLE_ARB) {
+                config.srgb = srgb != 0;
+            }
+        }
+
         let profile_mask = match config.profile {
             Profile::Core => WGL_CONTEXT_CORE_PROFILE_BIT_ARB,
             Profile::Compatibility => WGL_CONTEXT_COMPATIBILITY_PROFILE_BIT_ARB,
+            Profile::Es => WGL_CONTEXT_ES2_PROFILE_BIT_EXT,
         };
 
         #[rustfmt::skip]
@@ -261,10 +316,19 @@ impl GlContext {
         let gl_library = LoadLibraryA(gl_library_name.as_ptr());
 
         wglMakeCurrent(hdc, hglrc);
-        wglSwapIntervalEXT.unwrap()(config.vsync as i32);
+        if let Some(wglSwapIntervalEXT) = wglSwapIntervalEXT {
+            wglSwapIntervalEXT(config.vsync as i32);
+        }
         wglMakeCurrent(hdc, std::ptr::null_mut());
 
-        Ok(GlContext { hwnd, hdc, hglrc, gl_library })
+        Ok(GlContext {
+            hwnd,
+            hdc,
+            hglrc,
+            gl_library,
+            config,
+            swap_interval_ext: wglSwapIntervalEXT,
+        })
     }
 
     pub unsafe fn make_current(&self) {
@@ -290,6 +354,35 @@ impl GlContext {
             SwapBuffers(self.hdc);
         }
     }
+
+    pub fn set_swap_interval(&self, interval: i32) -> Result<(), GlError> {
+        #[allow(non_snake_case)]
+        let wglSwapIntervalEXT = match self.swap_interval_ext {
+            Some(wglSwapIntervalEXT) => wglSwapIntervalEXT,
+            None => return Err(GlError::SwapIntervalNotSupported),
+        };
+
+        // The swap interval applies to the current context, so we'll temporarily make this context
+        // current if it isn't already
+        unsafe {
+            let previous_hdc = wglGetCurrentDC();
+            let previous_hglrc = wglGetCurrentContext();
+            wglMakeCurrent(self.hdc, self.hglrc);
+
+            // Negative intervals require `WGL_EXT_swap_control_tear`
+            if wglSwapIntervalEXT(interval) == 0 && interval < 0 {
+                wglSwapIntervalEXT(-interval);
+            }
+
+            wglMakeCurrent(previous_hdc, previous_hglrc);
+        }
+
+        Ok(())
+    }
+
+    pub fn config(&self) -> GlConfig {
+        self.config.clone()
+    }
 }
 
 impl Drop for GlContext {
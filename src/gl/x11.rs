@@ -1,12 +1,13 @@
-use std::ffi::{c_void, CString};
-use std::os::raw::{c_int, c_ulong};
+use std::ffi::c_void;
+use std::os::raw::c_ulong;
 
-use x11::glx;
 use x11::xlib;
 
-use super::{GlConfig, GlError, Profile};
+use super::{GlApi, GlConfig, GlError};
 
+mod egl;
 mod errors;
+mod glx;
 
 #[derive(Debug)]
 pub enum CreationFailedError {
@@ -16,6 +17,11 @@ pub enum CreationFailedError {
     MakeCurrentFailed,
     ContextCreationFailed,
     X11Error(errors::XLibError),
+    /// `libEGL.so` could not be loaded
+    EglNotFound,
+    /// The EGL implementation does not support the X11 or surfaceless platforms
+    EglPlatformNotSupported,
+    EglError(egl::EglError),
 }
 
 impl From<errors::XLibError> for GlError {
@@ -24,45 +30,22 @@ impl From<errors::XLibError> for GlError {
     }
 }
 
-// See https://www.khronos.org/registry/OpenGL/extensions/ARB/GLX_ARB_create_context.txt
-
-type GlXCreateContextAttribsARB = unsafe extern "C" fn(
-    dpy: *mut xlib::Display,
-    fbc: glx::GLXFBConfig,
-    share_context: glx::GLXContext,
-    direct: xlib::Bool,
-    attribs: *const c_int,
-) -> glx::GLXContext;
-
-// See https://www.khronos.org/registry/OpenGL/extensions/EXT/EXT_swap_control.txt
-
-type GlXSwapIntervalEXT =
-    unsafe extern "C" fn(dpy: *mut xlib::Display, drawable: glx::GLXDrawable, interval: i32);
-
-// See https://www.khronos.org/registry/OpenGL/extensions/ARB/ARB_framebuffer_sRGB.txt
-
-const GLX_FRAMEBUFFER_SRGB_CAPABLE_ARB: i32 = 0x20B2;
-
-fn get_proc_address(symbol: &str) -> *const c_void {
-    let symbol = CString::new(symbol).unwrap();
-    unsafe { glx::glXGetProcAddress(symbol.as_ptr() as *const u8).unwrap() as *const c_void }
-}
-
-pub struct GlContext {
-    window: c_ulong,
-    display: *mut xlib::_XDisplay,
-    context: glx::GLXContext,
+/// An OpenGL context created through either GLX or EGL, depending on [GlConfig::api].
+pub enum GlContext {
+    Glx(glx::GlContext),
+    Egl(egl::GlContext),
 }
 
 /// The frame buffer configuration along with the general OpenGL configuration to somewhat minimize
 /// misuse.
-pub struct FbConfig {
-    gl_config: GlConfig,
-    fb_config: *mut glx::__GLXFBConfigRec,
+pub enum FbConfig {
+    Glx(glx::FbConfig),
+    Egl(egl::FbConfig),
 }
 
 /// The configuration a window should be created with after calling
 /// [GlContext::get_fb_config_and_visual].
+#[derive(Clone, Copy)]
 pub struct WindowConfig {
     pub depth: u8,
     pub visual: u32,
@@ -84,160 +67,135 @@ impl GlContext {
             return Err(GlError::InvalidWindowHandle);
         }
 
-        errors::XErrorHandler::handle(display, |error_handler| {
-            #[allow(non_snake_case)]
-            let glXCreateContextAttribsARB: GlXCreateContextAttribsARB = {
-                let addr = get_proc_address("glXCreateContextAttribsARB");
-                if addr.is_null() {
-                    return Err(GlError::CreationFailed(CreationFailedError::GetProcAddressFailed));
-                } else {
-                    std::mem::transmute(addr)
-                }
-            };
-
-            #[allow(non_snake_case)]
-            let glXSwapIntervalEXT: GlXSwapIntervalEXT = {
-                let addr = get_proc_address("glXSwapIntervalEXT");
-                if addr.is_null() {
-                    return Err(GlError::CreationFailed(CreationFailedError::GetProcAddressFailed));
-                } else {
-                    std::mem::transmute(addr)
-                }
-            };
-
-            error_handler.check()?;
-
-            let profile_mask = match config.gl_config.profile {
-                Profile::Core => glx::arb::GLX_CONTEXT_CORE_PROFILE_BIT_ARB,
-                Profile::Compatibility => glx::arb::GLX_CONTEXT_COMPATIBILITY_PROFILE_BIT_ARB,
-            };
-
-            #[rustfmt::skip]
-                let ctx_attribs = [
-                glx::arb::GLX_CONTEXT_MAJOR_VERSION_ARB, config.gl_config.version.0 as i32,
-                glx::arb::GLX_CONTEXT_MINOR_VERSION_ARB, config.gl_config.version.1 as i32,
-                glx::arb::GLX_CONTEXT_PROFILE_MASK_ARB, profile_mask,
-                0,
-            ];
-
-            let context = glXCreateContextAttribsARB(
-                display,
-                config.fb_config,
-                std::ptr::null_mut(),
-                1,
-                ctx_attribs.as_ptr(),
-            );
-
-            error_handler.check()?;
-
-            if context.is_null() {
-                return Err(GlError::CreationFailed(CreationFailedError::ContextCreationFailed));
-            }
-
-            let res = glx::glXMakeCurrent(display, window, context);
-            error_handler.check()?;
-            if res == 0 {
-                return Err(GlError::CreationFailed(CreationFailedError::MakeCurrentFailed));
-            }
-
-            glXSwapIntervalEXT(display, window, config.gl_config.vsync as i32);
-            error_handler.check()?;
-
-            if glx::glXMakeCurrent(display, 0, std::ptr::null_mut()) == 0 {
-                error_handler.check()?;
-                return Err(GlError::CreationFailed(CreationFailedError::MakeCurrentFailed));
-            }
-
-            Ok(GlContext { window, display, context })
-        })
+        match config {
+            FbConfig::Glx(config) => glx::GlContext::create(window, display, config).map(Self::Glx),
+            FbConfig::Egl(config) => egl::GlContext::create(window, config).map(Self::Egl),
+        }
+    }
+
+    /// Create an EGL context without a window or an X11 connection using Mesa's surfaceless
+    /// platform.
+    pub unsafe fn create_headless(config: GlConfig) -> Result<GlContext, GlError> {
+        egl::GlContext::create_surfaceless(config).map(Self::Egl)
     }
 
     /// Find a matching framebuffer config and window visual for the given OpenGL configuration.
     /// This needs to be passed to [Self::create] along with a handle to a window that was created
-    /// using the visual also returned from this function.
+    /// using the visual also returned from this function. If `transparent` is set, then only
+    /// framebuffer configs with an alpha channel and a 32-bit ARGB visual are considered.
     pub unsafe fn get_fb_config_and_visual(
-        display: *mut xlib::_XDisplay, config: GlConfig,
+        display: *mut xlib::_XDisplay, mut config: GlConfig, transparent: bool,
     ) -> Result<(FbConfig, WindowConfig), GlError> {
-        errors::XErrorHandler::handle(display, |error_handler| {
-            let screen = xlib::XDefaultScreen(display);
-
-            #[rustfmt::skip]
-                let fb_attribs = [
-                glx::GLX_X_RENDERABLE, 1,
-                glx::GLX_X_VISUAL_TYPE, glx::GLX_TRUE_COLOR,
-                glx::GLX_DRAWABLE_TYPE, glx::GLX_WINDOW_BIT,
-                glx::GLX_RENDER_TYPE, glx::GLX_RGBA_BIT,
-                glx::GLX_RED_SIZE, config.red_bits as i32,
-                glx::GLX_GREEN_SIZE, config.green_bits as i32,
-                glx::GLX_BLUE_SIZE, config.blue_bits as i32,
-                glx::GLX_ALPHA_SIZE, config.alpha_bits as i32,
-                glx::GLX_DEPTH_SIZE, config.depth_bits as i32,
-                glx::GLX_STENCIL_SIZE, config.stencil_bits as i32,
-                glx::GLX_DOUBLEBUFFER, config.double_buffer as i32,
-                glx::GLX_SAMPLE_BUFFERS, config.samples.is_some() as i32,
-                glx::GLX_SAMPLES, config.samples.unwrap_or(0) as i32,
-                GLX_FRAMEBUFFER_SRGB_CAPABLE_ARB, config.srgb as i32,
-                0,
-            ];
-
-            let mut n_configs = 0;
-            let fb_config =
-                glx::glXChooseFBConfig(display, screen, fb_attribs.as_ptr(), &mut n_configs);
-
-            error_handler.check()?;
-            if n_configs <= 0 || fb_config.is_null() {
-                return Err(GlError::CreationFailed(CreationFailedError::InvalidFBConfig));
-            }
-
-            // Now that we have a matching framebuffer config, we need to know which visual matches
-            // thsi config so the window is compatible with the OpenGL context we're about to create
-            let fb_config = *fb_config;
-            let visual = glx::glXGetVisualFromFBConfig(display, fb_config);
-            if visual.is_null() {
-                return Err(GlError::CreationFailed(CreationFailedError::NoVisual));
-            }
-
-            Ok((
-                FbConfig { fb_config, gl_config: config },
-                WindowConfig { depth: (*visual).depth as u8, visual: (*visual).visualid as u32 },
-            ))
-        })
+        if transparent {
+            config.alpha_bits = config.alpha_bits.max(8);
+        }
+
+        match config.api {
+            GlApi::Glx => glx::GlContext::get_fb_config_and_visual(display, config, transparent)
+                .map(|(fb_config, window_config)| (FbConfig::Glx(fb_config), window_config)),
+            GlApi::Egl => egl::GlContext::get_fb_config_and_visual(display, config, transparent)
+                .map(|(fb_config, window_config)| (FbConfig::Egl(fb_config), window_config)),
+        }
     }
 
     pub unsafe fn make_current(&self) {
-        errors::XErrorHandler::handle(self.display, |error_handler| {
-            let res = glx::glXMakeCurrent(self.display, self.window, self.context);
-            error_handler.check().unwrap();
-            if res == 0 {
-                panic!("make_current failed")
-            }
-        })
+        match self {
+            GlContext::Glx(context) => context.make_current(),
+            GlContext::Egl(context) => context.make_current(),
+        }
     }
 
     pub unsafe fn make_not_current(&self) {
-        errors::XErrorHandler::handle(self.display, |error_handler| {
-            let res = glx::glXMakeCurrent(self.display, 0, std::ptr::null_mut());
-            error_handler.check().unwrap();
-            if res == 0 {
-                panic!("make_not_current failed")
-            }
-        })
+        match self {
+            GlContext::Glx(context) => context.make_not_current(),
+            GlContext::Egl(context) => context.make_not_current(),
+        }
     }
 
     pub fn get_proc_address(&self, symbol: &str) -> *const c_void {
-        get_proc_address(symbol)
+        match self {
+            GlContext::Glx(context) => context.get_proc_address(symbol),
+            GlContext::Egl(context) => context.get_proc_address(symbol),
+        }
     }
 
     pub fn swap_buffers(&self) {
-        errors::XErrorHandler::handle(self.display, |error_handler| {
-            unsafe {
-                glx::glXSwapBuffers(self.display, self.window);
-            }
-            error_handler.check().unwrap();
-        })
+        match self {
+            GlContext::Glx(context) => context.swap_buffers(),
+            GlContext::Egl(context) => context.swap_buffers(),
+        }
+    }
+
+    pub fn set_swap_interval(&self, interval: i32) -> Result<(), GlError> {
+        match self {
+            GlContext::Glx(context) => context.set_swap_interval(interval),
+            GlContext::Egl(context) => context.set_swap_interval(interval),
+        }
+    }
+
+    pub fn config(&self) -> GlConfig {
+        match self {
+            GlContext::Glx(context) => context.config(),
+            GlContext::Egl(context) => context.config(),
+        }
     }
 }
 
-impl Drop for GlContext {
-    fn drop(&mut self) {}
+/// How far a framebuffer config is removed from the requested configuration. Used by both backends
+/// to implement [super::ConfigSelection::PreferExact].
+fn config_distance(requested: &GlConfig, actual: &GlConfig) -> u32 {
+    // A mismatched flag weighs as much as a full byte worth of color bits
+    const FLAG_MISMATCH: u32 = 8;
+
+    let bits =
+        |requested: u8, actual: u8| (i32::from(requested) - i32::from(actual)).unsigned_abs();
+    let flag = |requested: bool, actual: bool| if requested == actual { 0 } else { FLAG_MISMATCH };
+
+    bits(requested.red_bits, actual.red_bits)
+        + bits(requested.green_bits, actual.green_bits)
+        + bits(requested.blue_bits, actual.blue_bits)
+        + bits(requested.alpha_bits, actual.alpha_bits)
+        + bits(requested.depth_bits, actual.depth_bits)
+        + bits(requested.stencil_bits, actual.stencil_bits)
+        + bits(requested.samples.unwrap_or(0), actual.samples.unwrap_or(0))
+        + flag(requested.srgb, actual.srgb)
+        + flag(requested.double_buffer, actual.double_buffer)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn config_distance_is_zero_for_exact_match() {
+        let config = GlConfig::default();
+
+        assert_eq!(config_distance(&config, &config), 0);
+    }
+
+    #[test]
+    fn config_distance_sums_bit_differences() {
+        let requested = GlConfig { samples: None, ..GlConfig::default() };
+        let actual =
+            GlConfig { red_bits: 10, depth_bits: 16, samples: Some(4), ..GlConfig::default() };
+
+        assert_eq!(config_distance(&requested, &actual), 2 + 8 + 4);
+    }
+
+    #[test]
+    fn config_distance_weighs_flag_mismatches() {
+        let requested = GlConfig::default();
+        let actual = GlConfig { srgb: false, double_buffer: false, ..GlConfig::default() };
+
+        assert_eq!(config_distance(&requested, &actual), 16);
+    }
+
+    #[test]
+    fn config_distance_prefers_closer_config() {
+        let requested = GlConfig { alpha_bits: 0, ..GlConfig::default() };
+        let closer = GlConfig { alpha_bits: 0, depth_bits: 32, ..GlConfig::default() };
+        let further = GlConfig { alpha_bits: 8, depth_bits: 32, ..GlConfig::default() };
+
+        assert!(config_distance(&requested, &closer) < config_distance(&requested, &further));
+    }
 }
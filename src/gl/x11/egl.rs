@@ -0,0 +1,647 @@
+use std::ffi::{c_void, CStr, CString};
+use std::fmt::{Debug, Formatter};
+use std::os::raw::{c_char, c_ulong};
+use std::sync::OnceLock;
+
+use x11::xlib;
+
+use super::{config_distance, CreationFailedError, WindowConfig};
+use crate::gl::{ConfigSelection, GlConfig, GlError, Profile};
+
+// See https://www.khronos.org/registry/EGL/api/EGL/egl.h
+
+type EGLBoolean = u32;
+type EGLenum = u32;
+type EGLint = i32;
+type EGLConfig = *mut c_void;
+type EGLContext = *mut c_void;
+type EGLDisplay = *mut c_void;
+type EGLSurface = *mut c_void;
+
+const EGL_FALSE: EGLBoolean = 0;
+const EGL_NO_CONTEXT: EGLContext = std::ptr::null_mut();
+const EGL_NO_DISPLAY: EGLDisplay = std::ptr::null_mut();
+const EGL_NO_SURFACE: EGLSurface = std::ptr::null_mut();
+
+const EGL_ALPHA_SIZE: EGLint = 0x3021;
+const EGL_BLUE_SIZE: EGLint = 0x3022;
+const EGL_GREEN_SIZE: EGLint = 0x3023;
+const EGL_RED_SIZE: EGLint = 0x3024;
+const EGL_DEPTH_SIZE: EGLint = 0x3025;
+const EGL_STENCIL_SIZE: EGLint = 0x3026;
+const EGL_NATIVE_VISUAL_ID: EGLint = 0x302E;
+const EGL_SAMPLES: EGLint = 0x3031;
+const EGL_SAMPLE_BUFFERS: EGLint = 0x3032;
+const EGL_SURFACE_TYPE: EGLint = 0x3033;
+const EGL_NONE: EGLint = 0x3038;
+const EGL_COLOR_BUFFER_TYPE: EGLint = 0x303F;
+const EGL_RENDERABLE_TYPE: EGLint = 0x3040;
+const EGL_EXTENSIONS: EGLint = 0x3055;
+const EGL_DRAW: EGLint = 0x3059;
+const EGL_READ: EGLint = 0x305A;
+const EGL_RENDER_BUFFER: EGLint = 0x3086;
+const EGL_SINGLE_BUFFER: EGLint = 0x3085;
+const EGL_RGB_BUFFER: EGLint = 0x308E;
+
+const EGL_WINDOW_BIT: EGLint = 0x0004;
+const EGL_OPENGL_ES2_BIT: EGLint = 0x0004;
+const EGL_OPENGL_BIT: EGLint = 0x0008;
+const EGL_OPENGL_ES3_BIT: EGLint = 0x0040;
+
+const EGL_OPENGL_ES_API: EGLenum = 0x30A0;
+const EGL_OPENGL_API: EGLenum = 0x30A2;
+
+const EGL_CONTEXT_MAJOR_VERSION: EGLint = 0x3098;
+const EGL_CONTEXT_MINOR_VERSION: EGLint = 0x30FB;
+const EGL_CONTEXT_OPENGL_PROFILE_MASK: EGLint = 0x30FD;
+const EGL_CONTEXT_OPENGL_CORE_PROFILE_BIT: EGLint = 0x0001;
+const EGL_CONTEXT_OPENGL_COMPATIBILITY_PROFILE_BIT: EGLint = 0x0002;
+
+// EGL is loaded at runtime so that applications using GLX don't need to have `libEGL.so` installed
+macro_rules! egl_functions {
+    ($(fn $name:ident($($arg:ident: $ty:ty),* $(,)?) -> $ret:ty;)*) => {
+        #[allow(non_snake_case)]
+        struct EglFunctions {
+            $($name: unsafe extern "C" fn($($ty),*) -> $ret,)*
+        }
+
+        impl EglFunctions {
+            unsafe fn load(library: *mut c_void) -> Option<EglFunctions> {
+                Some(EglFunctions {
+                    $($name: {
+                        let symbol = concat!(stringify!($name), "\0");
+                        let addr = libc::dlsym(library, symbol.as_ptr() as *const c_char);
+                        if addr.is_null() {
+                            return None;
+                        }
+
+                        std::mem::transmute::<*mut c_void, unsafe extern "C" fn($($ty),*) -> $ret>(
+                            addr,
+                        )
+                    },)*
+                })
+            }
+        }
+
+        $(
+            #[allow(non_snake_case)]
+            unsafe fn $name($($arg: $ty),*) -> $ret {
+                (egl_functions().$name)($($arg),*)
+            }
+        )*
+    };
+}
+
+egl_functions! {
+    fn eglGetError() -> EGLint;
+    fn eglQueryString(dpy: EGLDisplay, name: EGLint) -> *const c_char;
+    fn eglInitialize(dpy: EGLDisplay, major: *mut EGLint, minor: *mut EGLint) -> EGLBoolean;
+    fn eglChooseConfig(
+        dpy: EGLDisplay, attrib_list: *const EGLint, configs: *mut EGLConfig, config_size: EGLint,
+        num_config: *mut EGLint,
+    ) -> EGLBoolean;
+    fn eglGetConfigAttrib(
+        dpy: EGLDisplay, config: EGLConfig, attribute: EGLint, value: *mut EGLint,
+    ) -> EGLBoolean;
+    fn eglBindAPI(api: EGLenum) -> EGLBoolean;
+    fn eglCreateContext(
+        dpy: EGLDisplay, config: EGLConfig, share_context: EGLContext, attrib_list: *const EGLint,
+    ) -> EGLContext;
+    fn eglDestroyContext(dpy: EGLDisplay, ctx: EGLContext) -> EGLBoolean;
+    fn eglDestroySurface(dpy: EGLDisplay, surface: EGLSurface) -> EGLBoolean;
+    fn eglMakeCurrent(
+        dpy: EGLDisplay, draw: EGLSurface, read: EGLSurface, ctx: EGLContext,
+    ) -> EGLBoolean;
+    fn eglSwapBuffers(dpy: EGLDisplay, surface: EGLSurface) -> EGLBoolean;
+    fn eglSwapInterval(dpy: EGLDisplay, interval: EGLint) -> EGLBoolean;
+    fn eglGetProcAddress(procname: *const c_char) -> *const c_void;
+    fn eglGetCurrentContext() -> EGLContext;
+    fn eglGetCurrentDisplay() -> EGLDisplay;
+    fn eglGetCurrentSurface(readdraw: EGLint) -> EGLSurface;
+}
+
+static EGL_FUNCTIONS: OnceLock<Option<EglFunctions>> = OnceLock::new();
+
+/// Load `libEGL.so` and its functions the first time this is called. Returns false if EGL is not
+/// available.
+fn load_egl() -> bool {
+    EGL_FUNCTIONS
+        .get_or_init(|| unsafe {
+            ["libEGL.so.1\0", "libEGL.so\0"].iter().find_map(|name| {
+                let library = libc::dlopen(name.as_ptr() as *const c_char, libc::RTLD_NOW);
+                if library.is_null() {
+                    None
+                } else {
+                    // The library is never unloaded, so the function pointers stay valid
+                    EglFunctions::load(library)
+                }
+            })
+        })
+        .is_some()
+}
+
+/// The loaded EGL functions. Every EGL object is created from a display obtained through
+/// [get_platform_display], which makes sure EGL has been loaded.
+fn egl_functions() -> &'static EglFunctions {
+    EGL_FUNCTIONS.get().and_then(Option::as_ref).expect("EGL has not been loaded")
+}
+
+// See https://www.khronos.org/registry/EGL/extensions/EXT/EGL_EXT_platform_base.txt
+
+type EglGetPlatformDisplayEXT = unsafe extern "C" fn(
+    platform: EGLenum,
+    native_display: *mut c_void,
+    attrib_list: *const EGLint,
+) -> EGLDisplay;
+
+type EglCreatePlatformWindowSurfaceEXT = unsafe extern "C" fn(
+    dpy: EGLDisplay,
+    config: EGLConfig,
+    native_window: *mut c_void,
+    attrib_list: *const EGLint,
+) -> EGLSurface;
+
+// See https://www.khronos.org/registry/EGL/extensions/KHR/EGL_KHR_platform_x11.txt
+
+const EGL_PLATFORM_X11_KHR: EGLenum = 0x31D5;
+
+// See https://www.khronos.org/registry/EGL/extensions/MESA/EGL_MESA_platform_surfaceless.txt
+
+const EGL_PLATFORM_SURFACELESS_MESA: EGLenum = 0x31DD;
+
+// See https://www.khronos.org/registry/EGL/extensions/KHR/EGL_KHR_gl_colorspace.txt
+
+const EGL_GL_COLORSPACE_KHR: EGLint = 0x309D;
+const EGL_GL_COLORSPACE_SRGB_KHR: EGLint = 0x3089;
+
+/// An error code returned by `eglGetError()`.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub struct EglError(EGLint);
+
+impl EglError {
+    fn last() -> Self {
+        EglError(unsafe { eglGetError() })
+    }
+
+    pub fn code(&self) -> i32 {
+        self.0
+    }
+}
+
+impl Debug for EglError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let name = match self.0 {
+            0x3001 => "EGL_NOT_INITIALIZED",
+            0x3002 => "EGL_BAD_ACCESS",
+            0x3003 => "EGL_BAD_ALLOC",
+            0x3004 => "EGL_BAD_ATTRIBUTE",
+            0x3005 => "EGL_BAD_CONFIG",
+            0x3006 => "EGL_BAD_CONTEXT",
+            0x3007 => "EGL_BAD_CURRENT_SURFACE",
+            0x3008 => "EGL_BAD_DISPLAY",
+            0x3009 => "EGL_BAD_MATCH",
+            0x300A => "EGL_BAD_NATIVE_PIXMAP",
+            0x300B => "EGL_BAD_NATIVE_WINDOW",
+            0x300C => "EGL_BAD_PARAMETER",
+            0x300D => "EGL_BAD_SURFACE",
+            0x300E => "EGL_CONTEXT_LOST",
+            _ => "unknown",
+        };
+
+        f.debug_struct("EglError").field("error_code", &self.0).field("name", &name).finish()
+    }
+}
+
+impl From<EglError> for GlError {
+    fn from(e: EglError) -> Self {
+        GlError::CreationFailed(CreationFailedError::EglError(e))
+    }
+}
+
+fn get_proc_address(symbol: &str) -> *const c_void {
+    let symbol = CString::new(symbol).unwrap();
+    unsafe { eglGetProcAddress(symbol.as_ptr()) }
+}
+
+/// The space separated extension string for `display`. Passing `EGL_NO_DISPLAY` returns the client
+/// extensions.
+unsafe fn query_extensions(display: EGLDisplay) -> String {
+    let extensions = eglQueryString(display, EGL_EXTENSIONS);
+    if extensions.is_null() {
+        // This is also what happens when querying client extensions on EGL 1.4 without
+        // `EGL_EXT_client_extensions`, so this needs to be cleared
+        eglGetError();
+        return String::new();
+    }
+
+    CStr::from_ptr(extensions).to_string_lossy().into_owned()
+}
+
+fn has_extension(extensions: &str, name: &str) -> bool {
+    extensions.split(' ').any(|extension| extension == name)
+}
+
+/// Get and initialize the EGL display for a native display on the given platform.
+unsafe fn get_platform_display(
+    platform: EGLenum, native_display: *mut c_void,
+) -> Result<EGLDisplay, GlError> {
+    if !load_egl() {
+        return Err(GlError::CreationFailed(CreationFailedError::EglNotFound));
+    }
+
+    let client_extensions = query_extensions(EGL_NO_DISPLAY);
+    let platform_supported = match platform {
+        EGL_PLATFORM_X11_KHR => {
+            has_extension(&client_extensions, "EGL_KHR_platform_x11")
+                || has_extension(&client_extensions, "EGL_EXT_platform_x11")
+        }
+        EGL_PLATFORM_SURFACELESS_MESA => {
+            has_extension(&client_extensions, "EGL_MESA_platform_surfaceless")
+        }
+        _ => false,
+    };
+    if !platform_supported || !has_extension(&client_extensions, "EGL_EXT_platform_base") {
+        return Err(GlError::CreationFailed(CreationFailedError::EglPlatformNotSupported));
+    }
+
+    #[allow(non_snake_case)]
+    let eglGetPlatformDisplayEXT: EglGetPlatformDisplayEXT = {
+        let addr = get_proc_address("eglGetPlatformDisplayEXT");
+        if addr.is_null() {
+            return Err(GlError::CreationFailed(CreationFailedError::GetProcAddressFailed));
+        } else {
+            std::mem::transmute::<*const c_void, EglGetPlatformDisplayEXT>(addr)
+        }
+    };
+
+    let attribs = [EGL_NONE];
+    let display = eglGetPlatformDisplayEXT(platform, native_display, attribs.as_ptr());
+    if display == EGL_NO_DISPLAY {
+        return Err(EglError::last().into());
+    }
+
+    let (mut major, mut minor) = (0, 0);
+    if eglInitialize(display, &mut major, &mut minor) == EGL_FALSE {
+        return Err(EglError::last().into());
+    }
+
+    Ok(display)
+}
+
+/// The client API and renderable type bit needed for the requested profile.
+fn client_api(config: &GlConfig) -> (EGLenum, EGLint) {
+    match config.profile {
+        Profile::Es if config.version.0 >= 3 => (EGL_OPENGL_ES_API, EGL_OPENGL_ES3_BIT),
+        Profile::Es => (EGL_OPENGL_ES_API, EGL_OPENGL_ES2_BIT),
+        Profile::Core | Profile::Compatibility => (EGL_OPENGL_API, EGL_OPENGL_BIT),
+    }
+}
+
+/// Choose an `EGLConfig` matching `config` that can be used with surfaces of type `surface_type`,
+/// among the configs for which `accept` returns true.
+unsafe fn choose_config(
+    display: EGLDisplay, config: &GlConfig, surface_type: EGLint, srgb_supported: bool,
+    accept: impl Fn(EGLConfig) -> bool,
+) -> Result<EGLConfig, GlError> {
+    let (_, renderable_type) = client_api(config);
+
+    #[rustfmt::skip]
+    let config_attribs = [
+        EGL_SURFACE_TYPE, surface_type,
+        EGL_RENDERABLE_TYPE, renderable_type,
+        EGL_COLOR_BUFFER_TYPE, EGL_RGB_BUFFER,
+        EGL_RED_SIZE, config.red_bits as i32,
+        EGL_GREEN_SIZE, config.green_bits as i32,
+        EGL_BLUE_SIZE, config.blue_bits as i32,
+        EGL_ALPHA_SIZE, config.alpha_bits as i32,
+        EGL_DEPTH_SIZE, config.depth_bits as i32,
+        EGL_STENCIL_SIZE, config.stencil_bits as i32,
+        EGL_SAMPLE_BUFFERS, config.samples.is_some() as i32,
+        EGL_SAMPLES, config.samples.unwrap_or(0) as i32,
+        EGL_NONE,
+    ];
+
+    let mut n_configs = 0;
+    if eglChooseConfig(display, config_attribs.as_ptr(), std::ptr::null_mut(), 0, &mut n_configs)
+        == EGL_FALSE
+    {
+        return Err(EglError::last().into());
+    }
+    if n_configs <= 0 {
+        return Err(GlError::CreationFailed(CreationFailedError::InvalidFBConfig));
+    }
+
+    let mut candidates = vec![std::ptr::null_mut(); n_configs as usize];
+    if eglChooseConfig(
+        display,
+        config_attribs.as_ptr(),
+        candidates.as_mut_ptr(),
+        n_configs,
+        &mut n_configs,
+    ) == EGL_FALSE
+    {
+        return Err(EglError::last().into());
+    }
+    candidates.truncate(n_configs as usize);
+    candidates.retain(|&candidate| accept(candidate));
+
+    // Just like `glXChooseFBConfig()` the results are sorted by EGL's own criteria, and all sizes
+    // are treated as minimums
+    let egl_config = match config.selection {
+        ConfigSelection::AtLeast => candidates.first().copied(),
+        ConfigSelection::PreferExact => candidates.iter().copied().min_by_key(|candidate| {
+            config_distance(config, &query_config(display, *candidate, config, srgb_supported))
+        }),
+    };
+
+    egl_config.ok_or(GlError::CreationFailed(CreationFailedError::InvalidFBConfig))
+}
+
+/// The depth and ID of the X11 visual matching an `EGLConfig`, if it has one.
+unsafe fn get_window_config(
+    display: *mut xlib::_XDisplay, egl_display: EGLDisplay, egl_config: EGLConfig,
+) -> Option<WindowConfig> {
+    let mut visual_id = 0;
+    eglGetConfigAttrib(egl_display, egl_config, EGL_NATIVE_VISUAL_ID, &mut visual_id);
+    if visual_id == 0 {
+        return None;
+    }
+
+    let mut visual_template: xlib::XVisualInfo = std::mem::zeroed();
+    visual_template.visualid = visual_id as xlib::VisualID;
+    let mut n_visuals = 0;
+    let visual =
+        xlib::XGetVisualInfo(display, xlib::VisualIDMask, &mut visual_template, &mut n_visuals);
+    if visual.is_null() || n_visuals <= 0 {
+        return None;
+    }
+
+    let window_config =
+        WindowConfig { depth: (*visual).depth as u8, visual: (*visual).visualid as u32 };
+    xlib::XFree(visual as *mut c_void);
+
+    Some(window_config)
+}
+
+/// Read back the attributes of an `EGLConfig`. Whether the surface is double buffered and uses an
+/// sRGB color space is decided when creating the surface and not by the config. The context related
+/// options are copied from `requested`.
+unsafe fn query_config(
+    display: EGLDisplay, egl_config: EGLConfig, requested: &GlConfig, srgb_supported: bool,
+) -> GlConfig {
+    let attrib = |attribute: EGLint| {
+        let mut value = 0;
+        eglGetConfigAttrib(display, egl_config, attribute, &mut value);
+        value
+    };
+
+    let samples =
+        if attrib(EGL_SAMPLE_BUFFERS) > 0 { Some(attrib(EGL_SAMPLES) as u8) } else { None };
+
+    GlConfig {
+        red_bits: attrib(EGL_RED_SIZE) as u8,
+        blue_bits: attrib(EGL_BLUE_SIZE) as u8,
+        green_bits: attrib(EGL_GREEN_SIZE) as u8,
+        alpha_bits: attrib(EGL_ALPHA_SIZE) as u8,
+        depth_bits: attrib(EGL_DEPTH_SIZE) as u8,
+        stencil_bits: attrib(EGL_STENCIL_SIZE) as u8,
+        samples,
+        srgb: requested.srgb && srgb_supported,
+        ..requested.clone()
+    }
+}
+
+/// The EGL config along with the general OpenGL configuration to somewhat minimize misuse.
+pub struct FbConfig {
+    gl_config: GlConfig,
+    display: EGLDisplay,
+    egl_config: EGLConfig,
+}
+
+pub struct GlContext {
+    display: EGLDisplay,
+    /// `EGL_NO_SURFACE` for surfaceless contexts.
+    surface: EGLSurface,
+    context: EGLContext,
+    api: EGLenum,
+    config: GlConfig,
+}
+
+impl GlContext {
+    pub unsafe fn create(window: c_ulong, config: FbConfig) -> Result<GlContext, GlError> {
+        let FbConfig { gl_config, display, egl_config } = config;
+
+        #[allow(non_snake_case)]
+        let eglCreatePlatformWindowSurfaceEXT: EglCreatePlatformWindowSurfaceEXT = {
+            let addr = get_proc_address("eglCreatePlatformWindowSurfaceEXT");
+            if addr.is_null() {
+                return Err(GlError::CreationFailed(CreationFailedError::GetProcAddressFailed));
+            } else {
+                std::mem::transmute::<*const c_void, EglCreatePlatformWindowSurfaceEXT>(addr)
+            }
+        };
+
+        let mut surface_attribs = Vec::with_capacity(5);
+        if gl_config.srgb {
+            surface_attribs.extend([EGL_GL_COLORSPACE_KHR, EGL_GL_COLORSPACE_SRGB_KHR]);
+        }
+        if !gl_config.double_buffer {
+            surface_attribs.extend([EGL_RENDER_BUFFER, EGL_SINGLE_BUFFER]);
+        }
+        surface_attribs.push(EGL_NONE);
+
+        // With `EGL_KHR_platform_x11` the native window is a pointer to the X11 window ID
+        let mut native_window = window;
+        let surface = eglCreatePlatformWindowSurfaceEXT(
+            display,
+            egl_config,
+            &mut native_window as *mut c_ulong as *mut c_void,
+            surface_attribs.as_ptr(),
+        );
+        if surface == EGL_NO_SURFACE {
+            return Err(EglError::last().into());
+        }
+
+        let context = match Self::create_context(display, egl_config, surface, gl_config) {
+            Ok(context) => context,
+            Err(err) => {
+                eglDestroySurface(display, surface);
+                return Err(err);
+            }
+        };
+
+        // A missing swap control extension or not being able to disable vsync should not prevent
+        // the context from being created
+        eglMakeCurrent(display, surface, surface, context.context);
+        eglSwapInterval(display, context.config.vsync as i32);
+        eglMakeCurrent(display, EGL_NO_SURFACE, EGL_NO_SURFACE, EGL_NO_CONTEXT);
+
+        Ok(context)
+    }
+
+    /// Create a context on Mesa's surfaceless platform. This doesn't need an X11 server.
+    pub unsafe fn create_surfaceless(config: GlConfig) -> Result<GlContext, GlError> {
+        let display = get_platform_display(EGL_PLATFORM_SURFACELESS_MESA, std::ptr::null_mut())?;
+        if !has_extension(&query_extensions(display), "EGL_KHR_surfaceless_context") {
+            return Err(GlError::CreationFailed(CreationFailedError::EglPlatformNotSupported));
+        }
+
+        // There is no default framebuffer, so we can't make any guarantees about its format
+        let egl_config = choose_config(display, &config, 0, false, |_| true)?;
+        let gl_config = GlConfig {
+            double_buffer: false,
+            srgb: false,
+            ..query_config(display, egl_config, &config, false)
+        };
+
+        Self::create_context(display, egl_config, EGL_NO_SURFACE, gl_config)
+    }
+
+    unsafe fn create_context(
+        display: EGLDisplay, egl_config: EGLConfig, surface: EGLSurface, gl_config: GlConfig,
+    ) -> Result<GlContext, GlError> {
+        let (api, _) = client_api(&gl_config);
+        if eglBindAPI(api) == EGL_FALSE {
+            return Err(EglError::last().into());
+        }
+
+        let mut ctx_attribs = vec![
+            EGL_CONTEXT_MAJOR_VERSION,
+            gl_config.version.0 as i32,
+            EGL_CONTEXT_MINOR_VERSION,
+            gl_config.version.1 as i32,
+        ];
+        match gl_config.profile {
+            Profile::Core => ctx_attribs
+                .extend([EGL_CONTEXT_OPENGL_PROFILE_MASK, EGL_CONTEXT_OPENGL_CORE_PROFILE_BIT]),
+            Profile::Compatibility => ctx_attribs.extend([
+                EGL_CONTEXT_OPENGL_PROFILE_MASK,
+                EGL_CONTEXT_OPENGL_COMPATIBILITY_PROFILE_BIT,
+            ]),
+            Profile::Es => (),
+        }
+        ctx_attribs.push(EGL_NONE);
+
+        let context = eglCreateContext(display, egl_config, EGL_NO_CONTEXT, ctx_attribs.as_ptr());
+        if context == EGL_NO_CONTEXT {
+            return Err(GlError::CreationFailed(CreationFailedError::ContextCreationFailed));
+        }
+
+        Ok(GlContext { display, surface, context, api, config: gl_config })
+    }
+
+    pub unsafe fn get_fb_config_and_visual(
+        display: *mut xlib::_XDisplay, config: GlConfig, transparent: bool,
+    ) -> Result<(FbConfig, WindowConfig), GlError> {
+        let egl_display = get_platform_display(EGL_PLATFORM_X11_KHR, display as *mut c_void)?;
+        let srgb_supported = has_extension(&query_extensions(egl_display), "EGL_KHR_gl_colorspace");
+
+        // The window needs to be created with the visual EGL expects for this config, and
+        // transparent windows need a 32-bit ARGB visual
+        let egl_config =
+            choose_config(egl_display, &config, EGL_WINDOW_BIT, srgb_supported, |candidate| {
+                get_window_config(display, egl_display, candidate)
+                    .is_some_and(|window_config| !transparent || window_config.depth == 32)
+            })?;
+        let window_config = get_window_config(display, egl_display, egl_config)
+            .ok_or(GlError::CreationFailed(CreationFailedError::NoVisual))?;
+
+        let gl_config = query_config(egl_display, egl_config, &config, srgb_supported);
+
+        Ok((FbConfig { gl_config, display: egl_display, egl_config }, window_config))
+    }
+
+    pub unsafe fn make_current(&self) {
+        eglBindAPI(self.api);
+        if eglMakeCurrent(self.display, self.surface, self.surface, self.context) == EGL_FALSE {
+            panic!("make_current failed: {:?}", EglError::last())
+        }
+    }
+
+    pub unsafe fn make_not_current(&self) {
+        eglBindAPI(self.api);
+        if eglMakeCurrent(self.display, EGL_NO_SURFACE, EGL_NO_SURFACE, EGL_NO_CONTEXT) == EGL_FALSE
+        {
+            panic!("make_not_current failed: {:?}", EglError::last())
+        }
+    }
+
+    pub fn get_proc_address(&self, symbol: &str) -> *const c_void {
+        get_proc_address(symbol)
+    }
+
+    pub fn swap_buffers(&self) {
+        if self.surface == EGL_NO_SURFACE {
+            return;
+        }
+
+        unsafe {
+            if eglSwapBuffers(self.display, self.surface) == EGL_FALSE {
+                panic!("swap_buffers failed: {:?}", EglError::last())
+            }
+        }
+    }
+
+    pub fn set_swap_interval(&self, interval: i32) -> Result<(), GlError> {
+        if self.surface == EGL_NO_SURFACE {
+            return Err(GlError::SwapIntervalNotSupported);
+        }
+
+        unsafe {
+            // `eglSwapInterval()` applies to the surface bound to the current context, so we may
+            // need to temporarily make this context current
+            eglBindAPI(self.api);
+            let previous_context = eglGetCurrentContext();
+            let switch_context = previous_context != self.context;
+            let previous_display = eglGetCurrentDisplay();
+            let previous_draw = eglGetCurrentSurface(EGL_DRAW);
+            let previous_read = eglGetCurrentSurface(EGL_READ);
+
+            if switch_context {
+                eglMakeCurrent(self.display, self.surface, self.surface, self.context);
+            }
+
+            // EGL has no adaptive vsync, and negative intervals would get clamped to 0
+            let result = if eglSwapInterval(self.display, interval.abs()) == EGL_FALSE {
+                Err(EglError::last().into())
+            } else {
+                Ok(())
+            };
+
+            if switch_context {
+                if previous_context == EGL_NO_CONTEXT {
+                    eglMakeCurrent(self.display, EGL_NO_SURFACE, EGL_NO_SURFACE, EGL_NO_CONTEXT);
+                } else {
+                    eglMakeCurrent(
+                        previous_display,
+                        previous_draw,
+                        previous_read,
+                        previous_context,
+                    );
+                }
+            }
+
+            result
+        }
+    }
+
+    pub fn config(&self) -> GlConfig {
+        self.config.clone()
+    }
+}
+
+impl Drop for GlContext {
+    fn drop(&mut self) {
+        unsafe {
+            eglBindAPI(self.api);
+            if eglGetCurrentContext() == self.context {
+                eglMakeCurrent(self.display, EGL_NO_SURFACE, EGL_NO_SURFACE, EGL_NO_CONTEXT);
+            }
+
+            eglDestroyContext(self.display, self.context);
+            if self.surface != EGL_NO_SURFACE {
+                eglDestroySurface(self.display, self.surface);
+            }
+        }
+    }
+}
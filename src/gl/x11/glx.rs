@@ -0,0 +1,398 @@
+use std::ffi::{c_void, CStr, CString};
+use std::os::raw::{c_int, c_uint, c_ulong};
+
+use x11::glx;
+use x11::xlib;
+
+use super::{config_distance, errors, CreationFailedError, WindowConfig};
+use crate::gl::{ConfigSelection, GlConfig, GlError, Profile};
+
+// See https://www.khronos.org/registry/OpenGL/extensions/ARB/GLX_ARB_create_context.txt
+
+type GlXCreateContextAttribsARB = unsafe extern "C" fn(
+    dpy: *mut xlib::Display,
+    fbc: glx::GLXFBConfig,
+    share_context: glx::GLXContext,
+    direct: xlib::Bool,
+    attribs: *const c_int,
+) -> glx::GLXContext;
+
+// See https://www.khronos.org/registry/OpenGL/extensions/EXT/EXT_swap_control.txt
+// and https://www.khronos.org/registry/OpenGL/extensions/EXT/GLX_EXT_swap_control_tear.txt
+
+type GlXSwapIntervalEXT =
+    unsafe extern "C" fn(dpy: *mut xlib::Display, drawable: glx::GLXDrawable, interval: i32);
+
+// See https://www.khronos.org/registry/OpenGL/extensions/MESA/GLX_MESA_swap_control.txt
+
+type GlXSwapIntervalMESA = unsafe extern "C" fn(interval: c_uint) -> c_int;
+
+// See https://www.khronos.org/registry/OpenGL/extensions/SGI/GLX_SGI_swap_control.txt
+
+type GlXSwapIntervalSGI = unsafe extern "C" fn(interval: c_int) -> c_int;
+
+// See https://www.khronos.org/registry/OpenGL/extensions/ARB/ARB_framebuffer_sRGB.txt
+
+const GLX_FRAMEBUFFER_SRGB_CAPABLE_ARB: i32 = 0x20B2;
+
+// See https://www.khronos.org/registry/OpenGL/extensions/EXT/GLX_EXT_create_context_es2_profile.txt
+
+const GLX_CONTEXT_ES2_PROFILE_BIT_EXT: i32 = 0x00000004;
+
+fn get_proc_address(symbol: &str) -> *const c_void {
+    let symbol = CString::new(symbol).unwrap();
+    unsafe { glx::glXGetProcAddress(symbol.as_ptr() as *const u8).unwrap() as *const c_void }
+}
+
+/// The swap control extension used to set the swap interval, in order of preference.
+#[derive(Clone, Copy)]
+enum SwapControl {
+    /// `GLX_EXT_swap_control`, optionally with `GLX_EXT_swap_control_tear` for adaptive vsync.
+    Ext { swap_interval: GlXSwapIntervalEXT, supports_tear: bool },
+    /// `GLX_MESA_swap_control`. Applies to the current context's drawable.
+    Mesa(GlXSwapIntervalMESA),
+    /// `GLX_SGI_swap_control`. Applies to the current context's drawable and cannot disable vsync.
+    Sgi(GlXSwapIntervalSGI),
+}
+
+impl SwapControl {
+    /// Find the best swap control extension supported by the display, if any.
+    unsafe fn load(display: *mut xlib::_XDisplay) -> Option<SwapControl> {
+        let extensions = glx::glXQueryExtensionsString(display, xlib::XDefaultScreen(display));
+        if extensions.is_null() {
+            return None;
+        }
+
+        let extensions = CStr::from_ptr(extensions).to_string_lossy();
+        let load = |extension: &str, symbol: &str| {
+            if extensions.split(' ').any(|supported| supported == extension) {
+                let addr = get_proc_address(symbol);
+                if !addr.is_null() {
+                    return Some(addr);
+                }
+            }
+
+            None
+        };
+
+        if let Some(addr) = load("GLX_EXT_swap_control", "glXSwapIntervalEXT") {
+            return Some(SwapControl::Ext {
+                swap_interval: std::mem::transmute::<*const c_void, GlXSwapIntervalEXT>(addr),
+                supports_tear: load("GLX_EXT_swap_control_tear", "glXSwapIntervalEXT").is_some(),
+            });
+        }
+
+        if let Some(addr) = load("GLX_MESA_swap_control", "glXSwapIntervalMESA") {
+            return Some(SwapControl::Mesa(std::mem::transmute::<
+                *const c_void,
+                GlXSwapIntervalMESA,
+            >(addr)));
+        }
+
+        if let Some(addr) = load("GLX_SGI_swap_control", "glXSwapIntervalSGI") {
+            return Some(SwapControl::Sgi(
+                std::mem::transmute::<*const c_void, GlXSwapIntervalSGI>(addr),
+            ));
+        }
+
+        None
+    }
+
+    /// Whether the context needs to be current for [Self::set_interval] to have an effect.
+    fn needs_current_context(&self) -> bool {
+        !matches!(self, SwapControl::Ext { .. })
+    }
+
+    /// Set the swap interval for `window`. Negative intervals fall back to regular vsync if
+    /// adaptive vsync is not supported.
+    unsafe fn set_interval(
+        &self, display: *mut xlib::_XDisplay, window: c_ulong, interval: i32,
+    ) -> Result<(), GlError> {
+        match *self {
+            SwapControl::Ext { swap_interval, supports_tear } => {
+                let interval = if supports_tear { interval } else { interval.abs() };
+                swap_interval(display, window, interval);
+            }
+            SwapControl::Mesa(swap_interval) => {
+                swap_interval(interval.unsigned_abs());
+            }
+            // `glXSwapIntervalSGI()` treats an interval of 0 as an error
+            SwapControl::Sgi(_) if interval == 0 => return Err(GlError::SwapIntervalNotSupported),
+            SwapControl::Sgi(swap_interval) => {
+                swap_interval(interval.abs());
+            }
+        }
+
+        Ok(())
+    }
+}
+
+pub struct GlContext {
+    window: c_ulong,
+    display: *mut xlib::_XDisplay,
+    context: glx::GLXContext,
+    config: GlConfig,
+    swap_control: Option<SwapControl>,
+}
+
+/// The frame buffer configuration along with the general OpenGL configuration to somewhat minimize
+/// misuse.
+pub struct FbConfig {
+    gl_config: GlConfig,
+    fb_config: *mut glx::__GLXFBConfigRec,
+}
+
+impl GlContext {
+    pub unsafe fn create(
+        window: c_ulong, display: *mut xlib::_XDisplay, config: FbConfig,
+    ) -> Result<GlContext, GlError> {
+        errors::XErrorHandler::handle(display, |error_handler| {
+            #[allow(non_snake_case)]
+            let glXCreateContextAttribsARB: GlXCreateContextAttribsARB = {
+                let addr = get_proc_address("glXCreateContextAttribsARB");
+                if addr.is_null() {
+                    return Err(GlError::CreationFailed(CreationFailedError::GetProcAddressFailed));
+                } else {
+                    std::mem::transmute(addr)
+                }
+            };
+
+            error_handler.check()?;
+
+            let profile_mask = match config.gl_config.profile {
+                Profile::Core => glx::arb::GLX_CONTEXT_CORE_PROFILE_BIT_ARB,
+                Profile::Compatibility => glx::arb::GLX_CONTEXT_COMPATIBILITY_PROFILE_BIT_ARB,
+                Profile::Es => GLX_CONTEXT_ES2_PROFILE_BIT_EXT,
+            };
+
+            #[rustfmt::skip]
+                let ctx_attribs = [
+                glx::arb::GLX_CONTEXT_MAJOR_VERSION_ARB, config.gl_config.version.0 as i32,
+                glx::arb::GLX_CONTEXT_MINOR_VERSION_ARB, config.gl_config.version.1 as i32,
+                glx::arb::GLX_CONTEXT_PROFILE_MASK_ARB, profile_mask,
+                0,
+            ];
+
+            let context = glXCreateContextAttribsARB(
+                display,
+                config.fb_config,
+                std::ptr::null_mut(),
+                1,
+                ctx_attribs.as_ptr(),
+            );
+
+            error_handler.check()?;
+
+            if context.is_null() {
+                return Err(GlError::CreationFailed(CreationFailedError::ContextCreationFailed));
+            }
+
+            let res = glx::glXMakeCurrent(display, window, context);
+            error_handler.check()?;
+            if res == 0 {
+                return Err(GlError::CreationFailed(CreationFailedError::MakeCurrentFailed));
+            }
+
+            // A missing swap control extension or not being able to disable vsync should not
+            // prevent the context from being created
+            let swap_control = SwapControl::load(display);
+            if let Some(swap_control) = swap_control {
+                let _ = swap_control.set_interval(display, window, config.gl_config.vsync as i32);
+                error_handler.check()?;
+            }
+
+            if glx::glXMakeCurrent(display, 0, std::ptr::null_mut()) == 0 {
+                error_handler.check()?;
+                return Err(GlError::CreationFailed(CreationFailedError::MakeCurrentFailed));
+            }
+
+            Ok(GlContext { window, display, context, config: config.gl_config, swap_control })
+        })
+    }
+
+    pub unsafe fn get_fb_config_and_visual(
+        display: *mut xlib::_XDisplay, config: GlConfig, transparent: bool,
+    ) -> Result<(FbConfig, WindowConfig), GlError> {
+        errors::XErrorHandler::handle(display, |error_handler| {
+            let screen = xlib::XDefaultScreen(display);
+
+            #[rustfmt::skip]
+                let fb_attribs = [
+                glx::GLX_X_RENDERABLE, 1,
+                glx::GLX_X_VISUAL_TYPE, glx::GLX_TRUE_COLOR,
+                glx::GLX_DRAWABLE_TYPE, glx::GLX_WINDOW_BIT,
+                glx::GLX_RENDER_TYPE, glx::GLX_RGBA_BIT,
+                glx::GLX_RED_SIZE, config.red_bits as i32,
+                glx::GLX_GREEN_SIZE, config.green_bits as i32,
+                glx::GLX_BLUE_SIZE, config.blue_bits as i32,
+                glx::GLX_ALPHA_SIZE, config.alpha_bits as i32,
+                glx::GLX_DEPTH_SIZE, config.depth_bits as i32,
+                glx::GLX_STENCIL_SIZE, config.stencil_bits as i32,
+                glx::GLX_DOUBLEBUFFER, config.double_buffer as i32,
+                glx::GLX_SAMPLE_BUFFERS, config.samples.is_some() as i32,
+                glx::GLX_SAMPLES, config.samples.unwrap_or(0) as i32,
+                GLX_FRAMEBUFFER_SRGB_CAPABLE_ARB, config.srgb as i32,
+                0,
+            ];
+
+            let mut n_configs = 0;
+            let fb_configs =
+                glx::glXChooseFBConfig(display, screen, fb_attribs.as_ptr(), &mut n_configs);
+
+            error_handler.check()?;
+            if n_configs <= 0 || fb_configs.is_null() {
+                return Err(GlError::CreationFailed(CreationFailedError::InvalidFBConfig));
+            }
+
+            // Now that we have matching framebuffer configs, we need to know which visuals match
+            // these configs so the window is compatible with the OpenGL context we're about to
+            // create. Transparent windows need a 32-bit ARGB visual.
+            let candidates: Vec<_> = std::slice::from_raw_parts(fb_configs, n_configs as usize)
+                .iter()
+                .filter_map(|&candidate| {
+                    get_window_config(display, candidate)
+                        .map(|window_config| (candidate, window_config))
+                })
+                .filter(|(_, window_config)| !transparent || window_config.depth == 32)
+                .collect();
+            xlib::XFree(fb_configs as *mut c_void);
+
+            // `glXChooseFBConfig()` treats most attributes as lower bounds and sorts the results
+            // using its own criteria, so the first config may have more samples or bits than asked
+            // for
+            let (fb_config, window_config) = match config.selection {
+                ConfigSelection::AtLeast => candidates.first(),
+                ConfigSelection::PreferExact => candidates.iter().min_by_key(|(candidate, _)| {
+                    config_distance(&config, &query_fb_config(display, *candidate, &config))
+                }),
+            }
+            .copied()
+            .ok_or(GlError::CreationFailed(CreationFailedError::NoVisual))?;
+
+            // From here on we'll report the attributes we actually got instead of the requested
+            // ones
+            let gl_config = query_fb_config(display, fb_config, &config);
+
+            Ok((FbConfig { fb_config, gl_config }, window_config))
+        })
+    }
+
+    pub unsafe fn make_current(&self) {
+        errors::XErrorHandler::handle(self.display, |error_handler| {
+            let res = glx::glXMakeCurrent(self.display, self.window, self.context);
+            error_handler.check().unwrap();
+            if res == 0 {
+                panic!("make_current failed")
+            }
+        })
+    }
+
+    pub unsafe fn make_not_current(&self) {
+        errors::XErrorHandler::handle(self.display, |error_handler| {
+            let res = glx::glXMakeCurrent(self.display, 0, std::ptr::null_mut());
+            error_handler.check().unwrap();
+            if res == 0 {
+                panic!("make_not_current failed")
+            }
+        })
+    }
+
+    pub fn get_proc_address(&self, symbol: &str) -> *const c_void {
+        get_proc_address(symbol)
+    }
+
+    pub fn swap_buffers(&self) {
+        errors::XErrorHandler::handle(self.display, |error_handler| {
+            unsafe {
+                glx::glXSwapBuffers(self.display, self.window);
+            }
+            error_handler.check().unwrap();
+        })
+    }
+
+    pub fn set_swap_interval(&self, interval: i32) -> Result<(), GlError> {
+        let swap_control = self.swap_control.ok_or(GlError::SwapIntervalNotSupported)?;
+
+        errors::XErrorHandler::handle(self.display, |error_handler| unsafe {
+            // The MESA and SGI extensions set the swap interval for whatever context is current,
+            // so we may need to temporarily make this context current
+            let previous_context = glx::glXGetCurrentContext();
+            let switch_context =
+                swap_control.needs_current_context() && previous_context != self.context;
+            let previous_display = glx::glXGetCurrentDisplay();
+            let previous_drawable = glx::glXGetCurrentDrawable();
+
+            if switch_context {
+                glx::glXMakeCurrent(self.display, self.window, self.context);
+            }
+
+            let result = swap_control.set_interval(self.display, self.window, interval);
+
+            if switch_context {
+                if previous_context.is_null() {
+                    glx::glXMakeCurrent(self.display, 0, std::ptr::null_mut());
+                } else {
+                    glx::glXMakeCurrent(previous_display, previous_drawable, previous_context);
+                }
+            }
+
+            error_handler.check()?;
+            result
+        })
+    }
+
+    pub fn config(&self) -> GlConfig {
+        self.config.clone()
+    }
+}
+
+/// The depth and ID of the visual matching a framebuffer config, if it has one.
+unsafe fn get_window_config(
+    display: *mut xlib::_XDisplay, fb_config: glx::GLXFBConfig,
+) -> Option<WindowConfig> {
+    let visual = glx::glXGetVisualFromFBConfig(display, fb_config);
+    if visual.is_null() {
+        return None;
+    }
+
+    let window_config =
+        WindowConfig { depth: (*visual).depth as u8, visual: (*visual).visualid as u32 };
+    xlib::XFree(visual as *mut c_void);
+
+    Some(window_config)
+}
+
+/// Read back the attributes of a framebuffer config. The context related options that aren't part
+/// of the framebuffer config, like the version and profile, are copied from `requested`.
+unsafe fn query_fb_config(
+    display: *mut xlib::_XDisplay, fb_config: glx::GLXFBConfig, requested: &GlConfig,
+) -> GlConfig {
+    let attrib = |attribute: c_int| {
+        let mut value = 0;
+        glx::glXGetFBConfigAttrib(display, fb_config, attribute, &mut value);
+        value
+    };
+
+    let samples = if attrib(glx::GLX_SAMPLE_BUFFERS) > 0 {
+        Some(attrib(glx::GLX_SAMPLES) as u8)
+    } else {
+        None
+    };
+
+    GlConfig {
+        red_bits: attrib(glx::GLX_RED_SIZE) as u8,
+        blue_bits: attrib(glx::GLX_BLUE_SIZE) as u8,
+        green_bits: attrib(glx::GLX_GREEN_SIZE) as u8,
+        alpha_bits: attrib(glx::GLX_ALPHA_SIZE) as u8,
+        depth_bits: attrib(glx::GLX_DEPTH_SIZE) as u8,
+        stencil_bits: attrib(glx::GLX_STENCIL_SIZE) as u8,
+        samples,
+        srgb: attrib(GLX_FRAMEBUFFER_SRGB_CAPABLE_ARB) != 0,
+        double_buffer: attrib(glx::GLX_DOUBLEBUFFER) != 0,
+        ..requested.clone()
+    }
+}
+
+impl Drop for GlContext {
+    fn drop(&mut self) {}
+}
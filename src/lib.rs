@@ -8,6 +8,7 @@ mod x11;
 mod clipboard;
 mod event;
 mod keyboard;
+mod monitor;
 mod mouse_cursor;
 mod window;
 mod window_info;
@@ -20,7 +21,8 @@ pub mod vk;
 
 pub use clipboard::*;
 pub use event::*;
-pub use mouse_cursor::MouseCursor;
+pub use monitor::*;
+pub use mouse_cursor::{CursorGrabMode, MouseCursor};
 pub use window::*;
 pub use window_info::*;
 pub use window_open_options::*;
@@ -246,12 +246,12 @@ extern "C" fn view_did_change_backing_properties(this: &Object, _: Sel, _: id) {
             scale_factor,
         );
 
-        let window_info = state.window_info.get();
+        let window_info = state.window_inner.window_info.get();
 
         // Only send the event when the window's size has actually changed to be in line with the
         // other platform implementations
         if new_window_info.physical_size() != window_info.physical_size() {
-            state.window_info.set(new_window_info);
+            state.window_inner.window_info.set(new_window_info);
             state.trigger_event(Event::Window(WindowEvent::Resized(new_window_info)));
         }
     }
@@ -5,10 +5,10 @@ use std::rc::Rc;
 
 use cocoa::appkit::{
     NSApp, NSApplication, NSApplicationActivationPolicyRegular, NSBackingStoreBuffered,
-    NSPasteboard, NSView, NSWindow, NSWindowStyleMask,
+    NSPasteboard, NSScreen, NSView, NSWindow, NSWindowStyleMask,
 };
-use cocoa::base::{id, nil, NO, YES};
-use cocoa::foundation::{NSAutoreleasePool, NSPoint, NSRect, NSSize, NSString};
+use cocoa::base::{id, nil, BOOL, NO, YES};
+use cocoa::foundation::{NSArray, NSAutoreleasePool, NSPoint, NSRect, NSSize, NSString};
 use core_foundation::runloop::{
     CFRunLoop, CFRunLoopTimer, CFRunLoopTimerContext, __CFRunLoopTimer, kCFRunLoopDefaultMode,
 };
@@ -22,8 +22,8 @@ use raw_window_handle::{
 };
 
 use crate::{
-    Event, EventStatus, MouseCursor, Size, WindowHandler, WindowInfo, WindowOpenOptions,
-    WindowScalePolicy,
+    Event, EventStatus, MonitorInfo, MouseCursor, PhyPoint, ScaleSource, Size, WindowHandler,
+    WindowInfo, WindowOpenOptions, WindowScalePolicy,
 };
 
 use super::keyboard::KeyboardState;
@@ -65,6 +65,8 @@ pub(super) struct WindowInner {
     ns_window: Cell<Option<id>>,
     /// Our subclassed NSView
     ns_view: id,
+    /// The last known window info for this window.
+    pub(super) window_info: Cell<WindowInfo>,
 
     #[cfg(feature = "opengl")]
     gl_context: Option<GlContext>,
@@ -138,7 +140,7 @@ impl<'a> Window<'a> {
 
         let scaling = match options.scale {
             WindowScalePolicy::ScaleFactor(scale) => scale,
-            WindowScalePolicy::SystemScaleFactor => 1.0,
+            WindowScalePolicy::SystemScaleFactor | WindowScalePolicy::Auto(_) => 1.0,
         };
 
         let window_info = WindowInfo::from_logical_size(options.size, scaling);
@@ -156,6 +158,7 @@ impl<'a> Window<'a> {
             ns_app: Cell::new(None),
             ns_window: Cell::new(None),
             ns_view,
+            window_info: Cell::new(window_info),
 
             #[cfg(feature = "opengl")]
             gl_context: options
@@ -163,7 +166,7 @@ impl<'a> Window<'a> {
                 .map(|gl_config| Self::create_gl_context(None, ns_view, gl_config)),
         };
 
-        let window_handle = Self::init(window_inner, window_info, build);
+        let window_handle = Self::init(window_inner, build);
 
         unsafe {
             let _: id = msg_send![handle.ns_view as *mut Object, addSubview: ns_view];
@@ -195,7 +198,7 @@ impl<'a> Window<'a> {
 
         let scaling = match options.scale {
             WindowScalePolicy::ScaleFactor(scale) => scale,
-            WindowScalePolicy::SystemScaleFactor => 1.0,
+            WindowScalePolicy::SystemScaleFactor | WindowScalePolicy::Auto(_) => 1.0,
         };
 
         let window_info = WindowInfo::from_logical_size(options.size, scaling);
@@ -231,6 +234,7 @@ impl<'a> Window<'a> {
             ns_app: Cell::new(Some(app)),
             ns_window: Cell::new(Some(ns_window)),
             ns_view,
+            window_info: Cell::new(window_info),
 
             #[cfg(feature = "opengl")]
             gl_context: options
@@ -238,7 +242,7 @@ impl<'a> Window<'a> {
                 .map(|gl_config| Self::create_gl_context(Some(ns_window), ns_view, gl_config)),
         };
 
-        let _ = Self::init(window_inner, window_info, build);
+        let _ = Self::init(window_inner, build);
 
         unsafe {
             ns_window.setContentView_(ns_view);
@@ -250,7 +254,7 @@ impl<'a> Window<'a> {
         }
     }
 
-    fn init<H, B>(window_inner: WindowInner, window_info: WindowInfo, build: B) -> WindowHandle
+    fn init<H, B>(window_inner: WindowInner, build: B) -> WindowHandle
     where
         H: WindowHandler + 'static,
         B: FnOnce(&mut crate::Window) -> H,
@@ -266,7 +270,6 @@ impl<'a> Window<'a> {
             window_handler: RefCell::new(window_handler),
             keyboard_state: KeyboardState::new(),
             frame_timer: Cell::new(None),
-            window_info: Cell::new(window_info),
         });
 
         let window_state_ptr = Rc::into_raw(Rc::clone(&window_state));
@@ -309,6 +312,89 @@ impl<'a> Window<'a> {
         }
     }
 
+    pub fn window_info(&self) -> WindowInfo {
+        self.inner.window_info.get()
+    }
+
+    pub fn outer_position(&self) -> PhyPoint {
+        unsafe {
+            // For parented windows this is the position of our view, since the host owns the window
+            let ns_window: id = msg_send![self.inner.ns_view, window];
+            if ns_window.is_null() {
+                return PhyPoint::new(0, 0);
+            }
+
+            let frame = match self.inner.ns_window.get() {
+                Some(ns_window) => NSWindow::frame(ns_window),
+                None => {
+                    let bounds: NSRect = msg_send![self.inner.ns_view, bounds];
+                    let rect: NSRect =
+                        msg_send![self.inner.ns_view, convertRect: bounds toView: nil];
+                    msg_send![ns_window, convertRectToScreen: rect]
+                }
+            };
+
+            // Cocoa's screen coordinates start at the bottom left corner of the primary screen
+            let scale = NSWindow::backingScaleFactor(ns_window);
+            let top = primary_screen_height() - (frame.origin.y + frame.size.height);
+
+            PhyPoint::new((frame.origin.x * scale).round() as i32, (top * scale).round() as i32)
+        }
+    }
+
+    pub fn set_outer_position(&mut self, position: PhyPoint) {
+        // Parented windows cannot be moved since the host owns the window
+        if let Some(ns_window) = self.inner.ns_window.get() {
+            unsafe {
+                let scale = NSWindow::backingScaleFactor(ns_window);
+                let top_left = NSPoint::new(
+                    position.x as f64 / scale,
+                    primary_screen_height() - position.y as f64 / scale,
+                );
+
+                ns_window.setFrameTopLeftPoint_(top_left);
+            }
+        }
+    }
+
+    pub fn has_focus(&self) -> bool {
+        unsafe {
+            let ns_window: id = msg_send![self.inner.ns_view, window];
+            if ns_window.is_null() {
+                return false;
+            }
+
+            // In parented mode the host's window can be the key window while one of the host's own
+            // views has keyboard focus
+            let is_key_window: BOOL = msg_send![ns_window, isKeyWindow];
+            let first_responder: id = msg_send![ns_window, firstResponder];
+
+            is_key_window == YES && first_responder == self.inner.ns_view
+        }
+    }
+
+    pub fn scale_factor(&self) -> f64 {
+        unsafe {
+            let ns_window: id = msg_send![self.inner.ns_view, window];
+            if ns_window.is_null() {
+                return self.inner.window_info.get().scale();
+            }
+
+            NSWindow::backingScaleFactor(ns_window)
+        }
+    }
+
+    pub fn scale_source(&self) -> Option<ScaleSource> {
+        // The view always follows the window's backing scale factor, see
+        // `view_did_change_backing_properties()`
+        Some(ScaleSource::System)
+    }
+
+    pub fn current_monitor(&self) -> Option<MonitorInfo> {
+        // Monitor enumeration is not implemented on this platform yet
+        None
+    }
+
     pub fn set_mouse_cursor(&mut self, _mouse_cursor: MouseCursor) {
         todo!()
     }
@@ -334,8 +420,6 @@ pub(super) struct WindowState {
     window_handler: RefCell<Box<dyn WindowHandler>>,
     keyboard_state: KeyboardState,
     frame_timer: Cell<Option<CFRunLoopTimer>>,
-    /// The last known window info for this window.
-    pub window_info: Cell<WindowInfo>,
 }
 
 impl WindowState {
@@ -419,3 +503,19 @@ pub fn copy_to_clipboard(string: &str) {
         pb.setString_forType(ns_str, cocoa::appkit::NSPasteboardTypeString);
     }
 }
+
+/// The height of the screen containing the menu bar, which is where Cocoa's screen coordinate system
+/// starts.
+unsafe fn primary_screen_height() -> f64 {
+    let screens = NSScreen::screens(nil);
+    if screens.count() == 0 {
+        return 0.0;
+    }
+
+    NSScreen::frame(screens.objectAtIndex(0)).size.height
+}
+
+pub fn monitors() -> Vec<MonitorInfo> {
+    // Monitor enumeration is not implemented on this platform yet
+    Vec::new()
+}
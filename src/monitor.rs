@@ -0,0 +1,46 @@
+use crate::{PhyPoint, PhySize};
+
+#[cfg(target_os = "macos")]
+use crate::macos as platform;
+#[cfg(target_os = "windows")]
+use crate::win as platform;
+#[cfg(target_os = "linux")]
+use crate::x11 as platform;
+
+/// The info about a monitor
+#[derive(Debug, Clone, PartialEq)]
+pub struct MonitorInfo {
+    /// The monitor's name, usually the name of the output it's connected to (e.g. `DP-1`)
+    pub name: String,
+    /// The position of the monitor's top left corner in the virtual desktop
+    pub position: PhyPoint,
+    /// The monitor's size in physical pixels
+    pub size: PhySize,
+    /// The position of the part of the monitor that isn't covered by panels and docks
+    pub work_area_position: PhyPoint,
+    /// The size of the part of the monitor that isn't covered by panels and docks
+    pub work_area_size: PhySize,
+    /// The system scale factor for windows on this monitor
+    pub scale: f64,
+    /// The refresh rate in Hz, if it is known
+    pub refresh_rate: Option<f64>,
+    /// Whether this is the primary monitor
+    pub is_primary: bool,
+}
+
+impl MonitorInfo {
+    /// Whether `point` lies on this monitor
+    pub fn contains(&self, point: PhyPoint) -> bool {
+        point.x >= self.position.x
+            && point.y >= self.position.y
+            && i64::from(point.x) < i64::from(self.position.x) + i64::from(self.size.width)
+            && i64::from(point.y) < i64::from(self.position.y) + i64::from(self.size.height)
+    }
+}
+
+/// All monitors that are currently connected and enabled. Returns an empty list if they could not
+/// be queried. This is currently only implemented on Linux, and always returns an empty list on
+/// Windows and macOS.
+pub fn monitors() -> Vec<MonitorInfo> {
+    platform::monitors()
+}
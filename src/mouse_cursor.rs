@@ -47,3 +47,16 @@ impl Default for MouseCursor {
         Self::Default
     }
 }
+
+/// Restricts where the mouse cursor can go, see [crate::Window::set_cursor_grab].
+#[derive(Debug, Eq, PartialEq, Clone, Copy)]
+pub enum CursorGrabMode {
+    /// The cursor can move freely.
+    None,
+    /// The cursor can't leave the window.
+    Confined,
+    /// The cursor stays in place. Mouse movement is reported through [crate::MouseEvent::RawMotion]
+    /// events instead of [crate::MouseEvent::CursorMoved] events. This is useful for dragging
+    /// knobs and sliders, usually combined with [MouseCursor::Hidden].
+    Locked,
+}
@@ -1,13 +1,17 @@
-use std::ffi::c_void;
+use std::fmt;
 use std::marker::PhantomData;
+use std::sync::Arc;
 
 use ash::vk;
-pub use ash::{Device, Instance};
+pub use ash::{Device, Entry, Instance};
 
 // On X11 creating the context is a two step process
 #[cfg(not(target_os = "linux"))]
 use raw_window_handle::HasRawWindowHandle;
 
+mod swapchain;
+pub use swapchain::{AcquiredImage, VkSwapchain};
+
 #[cfg(target_os = "windows")]
 mod win;
 #[cfg(target_os = "windows")]
@@ -26,30 +30,195 @@ use macos as platform;
 
 #[derive(Clone, Debug)]
 pub struct VkConfig {
+    /// The application name reported to the driver.
+    pub application_name: String,
+    /// The minimum Vulkan API version as a `(major, minor)` pair. Both the instance and the
+    /// physical device need to support at least this version.
     pub minimum_version: (u8, u8),
+    /// Enable `VK_LAYER_KHRONOS_validation`. Creating the context fails if the layer is not
+    /// installed.
     pub request_validation_layers: bool,
+    /// Install a `VK_EXT_debug_utils` messenger if the extension is available. Messages are sent
+    /// to [Self::debug_callback], or to the `log` crate if that is not set.
     pub use_default_debug_messenger: bool,
+    pub debug_callback: Option<VkDebugCallback>,
+    /// Require a queue family that supports transfers but not graphics, for example for
+    /// asynchronous uploads.
     pub required_dedicated_transfer_queue: bool,
+    /// Instance extensions to enable in addition to the surface extensions needed to present to
+    /// the window.
+    pub instance_extensions: Vec<String>,
+    /// Device extensions to enable. Physical devices that don't support all of these are skipped.
+    /// `VK_KHR_swapchain` is added automatically when [Self::swapchain] is set.
     pub required_device_extensions: Vec<String>,
+    /// If provided, then a swapchain will be created for the window's surface. You'll be able to
+    /// access it through [VkContext::swapchain] and [VkContext::swapchain_mut].
+    pub swapchain: Option<VkSwapchainConfig>,
+    /// Which physical device to use when there are multiple suitable devices.
+    pub device_selector: VkDeviceSelector,
+    /// If provided, then the context will use this instance and device instead of creating its
+    /// own, and only a surface (and optionally a swapchain) will be created for the window. All
+    /// other instance and device related options are ignored in that case.
+    pub shared_device: Option<VkSharedDevice>,
 }
 
 impl Default for VkConfig {
     fn default() -> Self {
         VkConfig {
+            application_name: String::from("baseview"),
             minimum_version: (1, 2),
             request_validation_layers: false,
             use_default_debug_messenger: true,
+            debug_callback: None,
             required_dedicated_transfer_queue: false,
-            required_device_extensions: vec![String::from("VK_KHR_swapchain"), String::from("VK_KHR_surface")],
+            instance_extensions: Vec::new(),
+            required_device_extensions: Vec::new(),
+            swapchain: Some(VkSwapchainConfig::default()),
+            device_selector: VkDeviceSelector::default(),
+            shared_device: None,
         }
     }
 }
 
+/// How to pick a physical device among the devices that meet the [VkConfig]'s requirements.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub enum VkDeviceSelector {
+    /// Prefer discrete GPUs, then integrated GPUs, then anything else.
+    #[default]
+    PreferDiscrete,
+    /// Prefer integrated GPUs, then discrete GPUs, then anything else. This is usually the more
+    /// power efficient option on laptops.
+    PreferIntegrated,
+    /// Use the first device whose name contains this string, ignoring case.
+    ByName(String),
+    /// Use the device with this `deviceUUID`. Requires [VkConfig::minimum_version] to be at least
+    /// 1.1.
+    ByUuid([u8; vk::UUID_SIZE]),
+}
+
+/// An existing instance and device to share between multiple windows, see
+/// [VkConfig::shared_device]. The instance needs to have `VK_KHR_surface` and the platform's
+/// surface extension enabled, and the device needs to have `VK_KHR_swapchain` enabled if a
+/// swapchain is requested. These objects are never destroyed by baseview, so they need to outlive
+/// every window that uses them.
+#[derive(Clone)]
+pub struct VkSharedDevice {
+    pub entry: Entry,
+    pub instance: Instance,
+    pub physical_device: vk::PhysicalDevice,
+    pub device: Device,
+    /// The present queue's family needs to be able to present to the window's surface.
+    pub queues: VkQueues,
+}
+
+impl fmt::Debug for VkSharedDevice {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("VkSharedDevice")
+            .field("instance", &self.instance.handle())
+            .field("physical_device", &self.physical_device)
+            .field("device", &self.device.handle())
+            .field("queues", &self.queues)
+            .finish()
+    }
+}
+
+/// Receives the messages from the debug messenger installed when
+/// [VkConfig::use_default_debug_messenger] is set. This may be called from any thread.
+#[derive(Clone)]
+pub struct VkDebugCallback(pub Arc<VkDebugCallbackFn>);
+
+pub type VkDebugCallbackFn = dyn Fn(&VkDebugMessage) + Send + Sync;
+
+/// A message received through the debug messenger.
+#[derive(Clone, Copy, Debug)]
+pub struct VkDebugMessage<'a> {
+    pub severity: vk::DebugUtilsMessageSeverityFlagsEXT,
+    pub types: vk::DebugUtilsMessageTypeFlagsEXT,
+    pub message: &'a str,
+}
+
+impl fmt::Debug for VkDebugCallback {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("VkDebugCallback")
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct VkSwapchainConfig {
+    /// Surface formats in order of preference. If the surface supports none of these, then the
+    /// surface's first supported format is used instead.
+    pub surface_formats: Vec<vk::SurfaceFormatKHR>,
+    /// Present modes in order of preference. Falls back to `FIFO`, which is always supported.
+    pub present_modes: Vec<vk::PresentModeKHR>,
+    /// The number of images to request. This is clamped to the range supported by the surface.
+    pub image_count: u32,
+    pub image_usage: vk::ImageUsageFlags,
+}
+
+impl Default for VkSwapchainConfig {
+    fn default() -> Self {
+        VkSwapchainConfig {
+            surface_formats: vec![
+                vk::SurfaceFormatKHR {
+                    format: vk::Format::B8G8R8A8_SRGB,
+                    color_space: vk::ColorSpaceKHR::SRGB_NONLINEAR,
+                },
+                vk::SurfaceFormatKHR {
+                    format: vk::Format::R8G8B8A8_SRGB,
+                    color_space: vk::ColorSpaceKHR::SRGB_NONLINEAR,
+                },
+            ],
+            present_modes: vec![vk::PresentModeKHR::FIFO],
+            image_count: 3,
+            image_usage: vk::ImageUsageFlags::COLOR_ATTACHMENT,
+        }
+    }
+}
 
 #[derive(Debug)]
 pub enum VkError {
     InvalidWindowHandle,
+    /// The instance or all physical devices support a lower version than
+    /// [VkConfig::minimum_version]
     VersionNotSupported,
+    /// A requested instance layer is not installed
+    LayerNotPresent(String),
+    /// A requested instance extension is not supported
+    ExtensionNotPresent(String),
+    /// A requested instance or device extension name contains a nul byte
+    InvalidName(String),
+    /// None of the physical devices meet the requirements from the [VkConfig], or the
+    /// [VkConfig::shared_device] cannot present to the window's surface
+    NoSuitableDevice,
+    /// The swapchain could not be (re)created because the window currently has a zero size, for
+    /// instance because it has been minimized. This will resolve itself after the next resize.
+    ZeroSizedSurface,
+    CreationFailed(vk::Result),
+}
+
+impl From<vk::Result> for VkError {
+    fn from(result: vk::Result) -> Self {
+        VkError::CreationFailed(result)
+    }
+}
+
+/// A queue created along with the [VkContext]'s device.
+#[derive(Clone, Copy, Debug)]
+pub struct VkQueue {
+    pub queue: vk::Queue,
+    /// The index of the queue family this queue belongs to.
+    pub family_index: u32,
+}
+
+/// The queues created along with the [VkContext]'s device. The graphics and present queues may be
+/// the same queue if the device has a queue family that supports both.
+#[derive(Clone, Copy, Debug)]
+pub struct VkQueues {
+    pub graphics: VkQueue,
+    pub present: VkQueue,
+    /// A queue from a family that supports transfers but not graphics, if the device has one. This
+    /// is always present when [VkConfig::required_dedicated_transfer_queue] is set.
+    pub transfer: Option<VkQueue>,
 }
 
 pub struct VkContext {
@@ -78,6 +247,52 @@ impl VkContext {
         self.context.get_device()
     }
 
+    pub fn entry(&self) -> &Entry {
+        self.context.entry()
+    }
+
+    pub fn instance(&self) -> &Instance {
+        self.context.instance()
+    }
+
+    pub fn physical_device(&self) -> vk::PhysicalDevice {
+        self.context.physical_device()
+    }
+
+    /// The surface for the window this context was created for.
+    pub fn surface(&self) -> vk::SurfaceKHR {
+        self.context.surface()
+    }
+
+    /// The index of the graphics queue's queue family. Shorthand for
+    /// `self.queues().graphics.family_index`.
+    pub fn queue_family_index(&self) -> u32 {
+        self.queues().graphics.family_index
+    }
+
+    /// The graphics, present, and optional transfer queues along with their queue family indices.
+    pub fn queues(&self) -> VkQueues {
+        self.context.queues()
+    }
+
+    /// The swapchain for the window's surface, if [VkConfig::swapchain] was set.
+    pub fn swapchain(&self) -> Option<&VkSwapchain> {
+        self.context.swapchain()
+    }
+
+    /// The swapchain for the window's surface, if [VkConfig::swapchain] was set. Acquiring and
+    /// presenting images requires mutable access since the swapchain may need to be recreated.
+    pub fn swapchain_mut(&mut self) -> Option<&mut VkSwapchain> {
+        self.context.swapchain_mut()
+    }
+
+    /// Let the swapchain know about the window's new physical size.
+    pub(crate) fn resize_swapchain(&mut self, size: crate::PhySize) {
+        if let Some(swapchain) = self.context.swapchain_mut() {
+            swapchain.resize(size);
+        }
+    }
+
     // pub unsafe fn make_current(&self) {
     //     self.context.make_current();
     // }
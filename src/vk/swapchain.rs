@@ -0,0 +1,433 @@
+use ash::extensions::khr::{Surface, Swapchain};
+use ash::{vk, Device, Entry, Instance};
+
+use super::{VkError, VkSwapchainConfig};
+use crate::PhySize;
+
+/// The result of [VkSwapchain::acquire_next_image].
+#[derive(Debug, Clone, Copy)]
+pub struct AcquiredImage {
+    /// The index into [VkSwapchain::images] and [VkSwapchain::image_views] of the image that was
+    /// acquired.
+    pub index: u32,
+    /// Whether the swapchain was recreated while acquiring this image. Any resources derived from
+    /// the swapchain's images, format, or extent (like framebuffers) need to be recreated when this
+    /// is set.
+    pub swapchain_recreated: bool,
+}
+
+/// A swapchain for the window's surface. This is created along with the [super::VkContext] when
+/// [super::VkConfig::swapchain] is set, and it is automatically recreated when the window gets
+/// resized or when the swapchain becomes out of date.
+pub struct VkSwapchain {
+    device: Device,
+    surface_loader: Surface,
+    loader: Swapchain,
+    pdevice: vk::PhysicalDevice,
+    surface: vk::SurfaceKHR,
+    config: VkSwapchainConfig,
+    /// Whether the window was created with an alpha channel, see [choose_composite_alpha].
+    transparent: bool,
+
+    swapchain: vk::SwapchainKHR,
+    format: vk::SurfaceFormatKHR,
+    present_mode: vk::PresentModeKHR,
+    composite_alpha: vk::CompositeAlphaFlagsKHR,
+    extent: vk::Extent2D,
+    images: Vec<vk::Image>,
+    image_views: Vec<vk::ImageView>,
+
+    /// The window's current physical size, used when the surface doesn't dictate an extent.
+    window_size: PhySize,
+    /// Set when the window has been resized or when presenting returned `ERROR_OUT_OF_DATE_KHR` or
+    /// `SUBOPTIMAL_KHR`. The swapchain will be recreated before acquiring the next image.
+    needs_recreation: bool,
+}
+
+impl VkSwapchain {
+    /// `transparent` should be set if the window has an alpha channel, see
+    /// [Self::composite_alpha].
+    #[allow(clippy::too_many_arguments)]
+    pub(crate) unsafe fn new(
+        entry: &Entry, instance: &Instance, device: &Device, pdevice: vk::PhysicalDevice,
+        surface: vk::SurfaceKHR, config: VkSwapchainConfig, window_size: PhySize,
+        transparent: bool,
+    ) -> Result<VkSwapchain, VkError> {
+        let mut swapchain = VkSwapchain {
+            device: device.clone(),
+            surface_loader: Surface::new(entry, instance),
+            loader: Swapchain::new(instance, device),
+            pdevice,
+            surface,
+            config,
+            transparent,
+
+            swapchain: vk::SwapchainKHR::null(),
+            format: vk::SurfaceFormatKHR::default(),
+            present_mode: vk::PresentModeKHR::FIFO,
+            composite_alpha: vk::CompositeAlphaFlagsKHR::OPAQUE,
+            extent: vk::Extent2D::default(),
+            images: Vec::new(),
+            image_views: Vec::new(),
+
+            window_size,
+            needs_recreation: false,
+        };
+        swapchain.recreate()?;
+
+        Ok(swapchain)
+    }
+
+    pub fn handle(&self) -> vk::SwapchainKHR {
+        self.swapchain
+    }
+
+    /// The `VK_KHR_swapchain` function pointers, in case you need to call any swapchain functions
+    /// not covered by this wrapper.
+    pub fn loader(&self) -> &Swapchain {
+        &self.loader
+    }
+
+    pub fn format(&self) -> vk::SurfaceFormatKHR {
+        self.format
+    }
+
+    pub fn present_mode(&self) -> vk::PresentModeKHR {
+        self.present_mode
+    }
+
+    /// How the swapchain's alpha channel is composited with the rest of the desktop. For
+    /// transparent windows this is `PRE_MULTIPLIED` or `POST_MULTIPLIED` if the surface supports
+    /// either of them, in which case the rendered colors need to be premultiplied accordingly.
+    pub fn composite_alpha(&self) -> vk::CompositeAlphaFlagsKHR {
+        self.composite_alpha
+    }
+
+    pub fn extent(&self) -> vk::Extent2D {
+        self.extent
+    }
+
+    pub fn images(&self) -> &[vk::Image] {
+        &self.images
+    }
+
+    /// 2D color views for [Self::images], in the same order.
+    pub fn image_views(&self) -> &[vk::ImageView] {
+        &self.image_views
+    }
+
+    /// Acquire the next image to render to. `semaphore` and `fence` (either of which may be null)
+    /// are signaled once the image is ready to be used. If the window has been resized since the
+    /// last frame or if the swapchain is out of date, then the swapchain is recreated first. In
+    /// that case [AcquiredImage::swapchain_recreated] will be set.
+    pub fn acquire_next_image(
+        &mut self, semaphore: vk::Semaphore, fence: vk::Fence,
+    ) -> Result<AcquiredImage, VkError> {
+        let mut swapchain_recreated = false;
+        loop {
+            if self.needs_recreation {
+                unsafe { self.recreate()? };
+                swapchain_recreated = true;
+            }
+
+            let result = unsafe {
+                self.loader.acquire_next_image(self.swapchain, u64::MAX, semaphore, fence)
+            };
+            match result {
+                Ok((index, suboptimal)) => {
+                    // The image is still usable, so we'll only recreate the swapchain on the next
+                    // frame
+                    self.needs_recreation |= suboptimal;
+
+                    return Ok(AcquiredImage { index, swapchain_recreated });
+                }
+                // Neither the semaphore nor the fence will have been signaled, so we can safely
+                // try again after recreating the swapchain
+                Err(vk::Result::ERROR_OUT_OF_DATE_KHR) if !swapchain_recreated => {
+                    self.needs_recreation = true;
+                }
+                Err(err) => return Err(err.into()),
+            }
+        }
+    }
+
+    /// Present the image with index `image_index` on `queue` after waiting on `wait_semaphores`.
+    /// If the swapchain turns out to be out of date or suboptimal it will be recreated on the next
+    /// call to [Self::acquire_next_image].
+    pub fn present(
+        &mut self, queue: vk::Queue, wait_semaphores: &[vk::Semaphore], image_index: u32,
+    ) -> Result<(), VkError> {
+        let swapchains = [self.swapchain];
+        let image_indices = [image_index];
+        let present_info = vk::PresentInfoKHR::builder()
+            .wait_semaphores(wait_semaphores)
+            .swapchains(&swapchains)
+            .image_indices(&image_indices);
+
+        match unsafe { self.loader.queue_present(queue, &present_info) } {
+            Ok(suboptimal) => {
+                self.needs_recreation |= suboptimal;
+                Ok(())
+            }
+            Err(vk::Result::ERROR_OUT_OF_DATE_KHR) => {
+                self.needs_recreation = true;
+                Ok(())
+            }
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    /// Called by the window when its physical size changes. The swapchain is recreated lazily on
+    /// the next call to [Self::acquire_next_image].
+    pub(crate) fn resize(&mut self, window_size: PhySize) {
+        if window_size != self.window_size {
+            self.window_size = window_size;
+            self.needs_recreation = true;
+        }
+    }
+
+    /// (Re)create the swapchain for the surface's current capabilities. This waits for the device
+    /// to become idle before destroying the old swapchain's image views.
+    unsafe fn recreate(&mut self) -> Result<(), VkError> {
+        let capabilities = self
+            .surface_loader
+            .get_physical_device_surface_capabilities(self.pdevice, self.surface)?;
+        let formats =
+            self.surface_loader.get_physical_device_surface_formats(self.pdevice, self.surface)?;
+        let present_modes = self
+            .surface_loader
+            .get_physical_device_surface_present_modes(self.pdevice, self.surface)?;
+
+        // A current extent of `u32::MAX` means that the swapchain decides the surface's size
+        let extent = if capabilities.current_extent.width == u32::MAX {
+            vk::Extent2D {
+                width: self.window_size.width.clamp(
+                    capabilities.min_image_extent.width,
+                    capabilities.max_image_extent.width,
+                ),
+                height: self.window_size.height.clamp(
+                    capabilities.min_image_extent.height,
+                    capabilities.max_image_extent.height,
+                ),
+            }
+        } else {
+            capabilities.current_extent
+        };
+        if extent.width == 0 || extent.height == 0 {
+            self.needs_recreation = true;
+            return Err(VkError::ZeroSizedSurface);
+        }
+
+        let format = choose_surface_format(&self.config.surface_formats, &formats)
+            .ok_or(VkError::CreationFailed(vk::Result::ERROR_FORMAT_NOT_SUPPORTED))?;
+        let present_mode = choose_present_mode(&self.config.present_modes, &present_modes);
+        let composite_alpha =
+            choose_composite_alpha(capabilities.supported_composite_alpha, self.transparent);
+
+        let mut image_count = self.config.image_count.max(capabilities.min_image_count);
+        if capabilities.max_image_count > 0 {
+            image_count = image_count.min(capabilities.max_image_count);
+        }
+
+        let pre_transform =
+            if capabilities.supported_transforms.contains(vk::SurfaceTransformFlagsKHR::IDENTITY) {
+                vk::SurfaceTransformFlagsKHR::IDENTITY
+            } else {
+                capabilities.current_transform
+            };
+
+        let old_swapchain = self.swapchain;
+        let swapchain_info = vk::SwapchainCreateInfoKHR::builder()
+            .surface(self.surface)
+            .min_image_count(image_count)
+            .image_format(format.format)
+            .image_color_space(format.color_space)
+            .image_extent(extent)
+            .image_array_layers(1)
+            .image_usage(self.config.image_usage)
+            .image_sharing_mode(vk::SharingMode::EXCLUSIVE)
+            .pre_transform(pre_transform)
+            .composite_alpha(composite_alpha)
+            .present_mode(present_mode)
+            .clipped(true)
+            .old_swapchain(old_swapchain);
+        let swapchain = self.loader.create_swapchain(&swapchain_info, None)?;
+
+        // The old swapchain's images may still be in use, so everything needs to be idle before we
+        // can get rid of them
+        if old_swapchain != vk::SwapchainKHR::null() {
+            self.device.device_wait_idle()?;
+        }
+        self.destroy();
+        self.swapchain = swapchain;
+
+        self.images = self.loader.get_swapchain_images(swapchain)?;
+        for &image in &self.images {
+            let view_info = vk::ImageViewCreateInfo::builder()
+                .image(image)
+                .view_type(vk::ImageViewType::TYPE_2D)
+                .format(format.format)
+                .subresource_range(vk::ImageSubresourceRange {
+                    aspect_mask: vk::ImageAspectFlags::COLOR,
+                    base_mip_level: 0,
+                    level_count: 1,
+                    base_array_layer: 0,
+                    layer_count: 1,
+                });
+            self.image_views.push(self.device.create_image_view(&view_info, None)?);
+        }
+
+        self.format = format;
+        self.present_mode = present_mode;
+        self.composite_alpha = composite_alpha;
+        self.extent = extent;
+        self.needs_recreation = false;
+
+        Ok(())
+    }
+
+    /// Destroy the current swapchain and its image views, if there are any.
+    unsafe fn destroy(&mut self) {
+        for image_view in self.image_views.drain(..) {
+            self.device.destroy_image_view(image_view, None);
+        }
+        self.images.clear();
+
+        if self.swapchain != vk::SwapchainKHR::null() {
+            self.loader.destroy_swapchain(self.swapchain, None);
+            self.swapchain = vk::SwapchainKHR::null();
+        }
+    }
+}
+
+/// Use the first preferred format the surface supports, or whatever the surface prefers otherwise.
+fn choose_surface_format(
+    preferred: &[vk::SurfaceFormatKHR], supported: &[vk::SurfaceFormatKHR],
+) -> Option<vk::SurfaceFormatKHR> {
+    preferred
+        .iter()
+        .find(|preferred| {
+            supported.iter().any(|format| {
+                format.format == preferred.format && format.color_space == preferred.color_space
+            })
+        })
+        .or_else(|| supported.first())
+        .copied()
+}
+
+/// Use the first preferred present mode the surface supports, or FIFO otherwise. FIFO is the only
+/// present mode that's required to be supported.
+fn choose_present_mode(
+    preferred: &[vk::PresentModeKHR], supported: &[vk::PresentModeKHR],
+) -> vk::PresentModeKHR {
+    preferred
+        .iter()
+        .find(|preferred| supported.contains(preferred))
+        .copied()
+        .unwrap_or(vk::PresentModeKHR::FIFO)
+}
+
+/// Pick a composite alpha mode the surface supports. Transparent windows prefer a mode that
+/// respects the alpha channel, while opaque windows prefer to ignore it. Which modes are available
+/// depends on the driver and compositor, and none of them is guaranteed.
+fn choose_composite_alpha(
+    supported: vk::CompositeAlphaFlagsKHR, transparent: bool,
+) -> vk::CompositeAlphaFlagsKHR {
+    use vk::CompositeAlphaFlagsKHR as Alpha;
+
+    let preferred: &[Alpha] = if transparent {
+        &[Alpha::PRE_MULTIPLIED, Alpha::POST_MULTIPLIED, Alpha::OPAQUE, Alpha::INHERIT]
+    } else {
+        &[Alpha::OPAQUE, Alpha::INHERIT, Alpha::PRE_MULTIPLIED, Alpha::POST_MULTIPLIED]
+    };
+
+    // Surfaces are required to support at least one of these
+    preferred.iter().copied().find(|&mode| supported.contains(mode)).unwrap_or(Alpha::OPAQUE)
+}
+
+impl Drop for VkSwapchain {
+    fn drop(&mut self) {
+        unsafe {
+            // Errors here would mean the device was lost, in which case there's nothing left to
+            // wait for
+            let _ = self.device.device_wait_idle();
+            self.destroy();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use vk::CompositeAlphaFlagsKHR as Alpha;
+
+    fn surface_format(format: vk::Format) -> vk::SurfaceFormatKHR {
+        vk::SurfaceFormatKHR { format, color_space: vk::ColorSpaceKHR::SRGB_NONLINEAR }
+    }
+
+    #[test]
+    fn choose_surface_format_prefers_requested_format() {
+        let preferred = [surface_format(vk::Format::B8G8R8A8_SRGB)];
+        let supported =
+            [surface_format(vk::Format::B8G8R8A8_UNORM), surface_format(vk::Format::B8G8R8A8_SRGB)];
+
+        assert_eq!(
+            choose_surface_format(&preferred, &supported),
+            Some(surface_format(vk::Format::B8G8R8A8_SRGB))
+        );
+    }
+
+    #[test]
+    fn choose_surface_format_falls_back_to_first_supported() {
+        let preferred = [surface_format(vk::Format::R16G16B16A16_SFLOAT)];
+        let supported = [surface_format(vk::Format::B8G8R8A8_UNORM)];
+
+        assert_eq!(choose_surface_format(&preferred, &supported), Some(supported[0]));
+        assert_eq!(choose_surface_format(&preferred, &[]), None);
+    }
+
+    #[test]
+    fn choose_present_mode_falls_back_to_fifo() {
+        let preferred = [vk::PresentModeKHR::MAILBOX, vk::PresentModeKHR::IMMEDIATE];
+
+        assert_eq!(
+            choose_present_mode(
+                &preferred,
+                &[vk::PresentModeKHR::FIFO, vk::PresentModeKHR::IMMEDIATE]
+            ),
+            vk::PresentModeKHR::IMMEDIATE
+        );
+        assert_eq!(
+            choose_present_mode(&preferred, &[vk::PresentModeKHR::FIFO]),
+            vk::PresentModeKHR::FIFO
+        );
+    }
+
+    #[test]
+    fn choose_composite_alpha_for_opaque_windows() {
+        assert_eq!(
+            choose_composite_alpha(Alpha::OPAQUE | Alpha::PRE_MULTIPLIED, false),
+            Alpha::OPAQUE
+        );
+        assert_eq!(
+            choose_composite_alpha(Alpha::INHERIT | Alpha::PRE_MULTIPLIED, false),
+            Alpha::INHERIT
+        );
+        assert_eq!(choose_composite_alpha(Alpha::POST_MULTIPLIED, false), Alpha::POST_MULTIPLIED);
+    }
+
+    #[test]
+    fn choose_composite_alpha_for_transparent_windows() {
+        let all = Alpha::OPAQUE | Alpha::PRE_MULTIPLIED | Alpha::POST_MULTIPLIED | Alpha::INHERIT;
+
+        assert_eq!(choose_composite_alpha(all, true), Alpha::PRE_MULTIPLIED);
+        assert_eq!(
+            choose_composite_alpha(Alpha::OPAQUE | Alpha::POST_MULTIPLIED, true),
+            Alpha::POST_MULTIPLIED
+        );
+        // Without a mode that respects the alpha channel the window ends up opaque
+        assert_eq!(choose_composite_alpha(Alpha::OPAQUE | Alpha::INHERIT, true), Alpha::OPAQUE);
+        assert_eq!(choose_composite_alpha(Alpha::INHERIT, true), Alpha::INHERIT);
+    }
+}
@@ -1,196 +1,596 @@
-// use std::ffi::{c_void, CString};
-// use std::os::raw::{c_int, c_ulong};
-
-// use x11::glx;
-// use x11::xlib;
-
-use std::ffi::CString;
+use std::ffi::{CStr, CString};
 use std::os::raw::c_ulong as other_c_ulong;
 use std::os::raw::c_void;
 use x11::xlib;
 
-// use ash::vk;
-// pub use ash::{Device, Instance};
-
-use super::{VkConfig, VkError};
+use super::{
+    VkConfig, VkDebugCallback, VkDebugMessage, VkDeviceSelector, VkError, VkQueue, VkQueues,
+    VkSharedDevice, VkSwapchain,
+};
+use crate::PhySize;
 
 use ash::extensions::{
     ext::DebugUtils,
     khr::{Surface, Swapchain, XlibSurface},
 };
 
-use ash::{vk, Entry};
-pub use ash::{Device, Instance};
-use raw_window_handle::{HasRawWindowHandle, RawWindowHandle};
-use std::ptr;
+use ash::vk;
+pub use ash::{Device, Entry, Instance};
 
-pub struct Init {
+const VALIDATION_LAYER: &str = "VK_LAYER_KHRONOS_validation";
+
+/// The queue families used by the context.
+#[derive(Debug, Clone, Copy)]
+struct QueueFamilies {
+    graphics: u32,
+    present: u32,
+    /// A queue family supporting transfers but not graphics, if the device has one.
+    transfer: Option<u32>,
+}
+
+impl QueueFamilies {
+    /// Find the queue families for `pdevice`, or `None` if it can't render or if none of its queue
+    /// families can present according to `supports_present`.
+    unsafe fn find(
+        instance: &Instance, pdevice: vk::PhysicalDevice, supports_present: impl Fn(u32) -> bool,
+        require_dedicated_transfer: bool,
+    ) -> Option<Self> {
+        let properties = instance.get_physical_device_queue_family_properties(pdevice);
+        let graphics_families = properties
+            .iter()
+            .enumerate()
+            .filter(|(_, info)| info.queue_flags.contains(vk::QueueFlags::GRAPHICS))
+            .map(|(index, _)| index as u32);
+
+        // Prefer a single queue family for both graphics and presentation
+        let (graphics, present) =
+            match graphics_families.clone().find(|&index| supports_present(index)) {
+                Some(index) => (index, index),
+                None => {
+                    let graphics = graphics_families.clone().next()?;
+                    let present =
+                        (0..properties.len() as u32).find(|&index| supports_present(index))?;
+
+                    (graphics, present)
+                }
+            };
+
+        // Prefer transfer-only queue families, these usually map to the GPU's DMA engines
+        let transfer_families = properties.iter().enumerate().filter(|(_, info)| {
+            info.queue_flags.contains(vk::QueueFlags::TRANSFER)
+                && !info.queue_flags.contains(vk::QueueFlags::GRAPHICS)
+        });
+        let transfer = transfer_families
+            .clone()
+            .find(|(_, info)| !info.queue_flags.contains(vk::QueueFlags::COMPUTE))
+            .or_else(|| transfer_families.clone().next())
+            .map(|(index, _)| index as u32);
+        if require_dedicated_transfer && transfer.is_none() {
+            return None;
+        }
+
+        Some(QueueFamilies { graphics, present, transfer })
+    }
+
+    /// The distinct queue family indices, in the order a queue should be created for them.
+    fn unique_indices(&self) -> Vec<u32> {
+        let mut indices = vec![self.graphics];
+        for index in std::iter::once(self.present).chain(self.transfer) {
+            if !indices.contains(&index) {
+                indices.push(index);
+            }
+        }
+
+        indices
+    }
+}
+
+/// Routes `VK_EXT_debug_utils` messages to the [VkDebugCallback], or to the `log` facade if there
+/// is no callback.
+struct DebugMessenger {
+    loader: DebugUtils,
+    messenger: vk::DebugUtilsMessengerEXT,
+    /// Passed to the driver as the user data pointer, so this needs to stay at a stable address
+    /// for as long as the messenger exists.
+    _callback: Box<Option<VkDebugCallback>>,
+}
+
+impl DebugMessenger {
+    unsafe fn new(
+        entry: &Entry, instance: &Instance, callback: Option<VkDebugCallback>,
+    ) -> Result<Self, vk::Result> {
+        let loader = DebugUtils::new(entry, instance);
+        let callback = Box::new(callback);
+
+        let messenger_info = vk::DebugUtilsMessengerCreateInfoEXT::builder()
+            .message_severity(
+                vk::DebugUtilsMessageSeverityFlagsEXT::ERROR
+                    | vk::DebugUtilsMessageSeverityFlagsEXT::WARNING
+                    | vk::DebugUtilsMessageSeverityFlagsEXT::INFO
+                    | vk::DebugUtilsMessageSeverityFlagsEXT::VERBOSE,
+            )
+            .message_type(
+                vk::DebugUtilsMessageTypeFlagsEXT::GENERAL
+                    | vk::DebugUtilsMessageTypeFlagsEXT::VALIDATION
+                    | vk::DebugUtilsMessageTypeFlagsEXT::PERFORMANCE,
+            )
+            .pfn_user_callback(Some(debug_messenger_callback))
+            .user_data(&*callback as *const Option<VkDebugCallback> as *mut c_void);
+        let messenger = loader.create_debug_utils_messenger(&messenger_info, None)?;
+
+        Ok(DebugMessenger { loader, messenger, _callback: callback })
+    }
+}
+
+impl Drop for DebugMessenger {
+    fn drop(&mut self) {
+        unsafe { self.loader.destroy_debug_utils_messenger(self.messenger, None) };
+    }
+}
+
+unsafe extern "system" fn debug_messenger_callback(
+    severity: vk::DebugUtilsMessageSeverityFlagsEXT, types: vk::DebugUtilsMessageTypeFlagsEXT,
+    callback_data: *const vk::DebugUtilsMessengerCallbackDataEXT, user_data: *mut c_void,
+) -> vk::Bool32 {
+    let message = if callback_data.is_null() || (*callback_data).p_message.is_null() {
+        String::new()
+    } else {
+        CStr::from_ptr((*callback_data).p_message).to_string_lossy().into_owned()
+    };
+
+    match &*(user_data as *const Option<VkDebugCallback>) {
+        Some(callback) => (callback.0)(&VkDebugMessage { severity, types, message: &message }),
+        None => {
+            let level = if severity.contains(vk::DebugUtilsMessageSeverityFlagsEXT::ERROR) {
+                log::Level::Error
+            } else if severity.contains(vk::DebugUtilsMessageSeverityFlagsEXT::WARNING) {
+                log::Level::Warn
+            } else if severity.contains(vk::DebugUtilsMessageSeverityFlagsEXT::INFO) {
+                log::Level::Info
+            } else {
+                log::Level::Debug
+            };
+
+            log::log!(level, "[Vulkan {:?}] {}", types, message);
+        }
+    }
+
+    // Returning true would abort the call that triggered the message
+    vk::FALSE
+}
+
+/// The instance level objects. Dropping this destroys them in the reverse order of creation, so
+/// anything created before a failing step in [Init::new] is cleaned up again.
+struct InstanceState {
     entry: Entry,
     instance: Instance,
+    debug_messenger: Option<DebugMessenger>,
+    surface_loader: Surface,
+    xlib_surface_loader: XlibSurface,
     surface: vk::SurfaceKHR,
-    //surface: XlibSurface,
-    pdevice: vk::PhysicalDevice,
-    queue_family_index: u32,
+    /// Whether the instance came from [VkConfig::shared_device], in which case only the surface
+    /// is ours to destroy.
+    shared: bool,
+}
+
+impl InstanceState {
+    fn new(entry: Entry, instance: Instance, shared: bool) -> Self {
+        InstanceState {
+            surface_loader: Surface::new(&entry, &instance),
+            xlib_surface_loader: XlibSurface::new(&entry, &instance),
+            entry,
+            instance,
+            debug_messenger: None,
+            surface: vk::SurfaceKHR::null(),
+            shared,
+        }
+    }
+
+    unsafe fn create_surface(
+        &mut self, window: other_c_ulong, display: *mut xlib::_XDisplay,
+    ) -> Result<(), VkError> {
+        let surface_info =
+            vk::XlibSurfaceCreateInfoKHR::builder().window(window).dpy(display as *mut _).build();
+
+        self.surface = self.xlib_surface_loader.create_xlib_surface(&surface_info, None)?;
+
+        Ok(())
+    }
+
+    /// Whether queue family `index` of `pdevice` can present to the window. This checks both the
+    /// surface and the window's visual, since a device may support the former but not the latter.
+    unsafe fn supports_present(
+        &self, pdevice: vk::PhysicalDevice, index: u32, display: *mut xlib::_XDisplay,
+        visual_id: vk::VisualID,
+    ) -> bool {
+        let surface_support = self
+            .surface_loader
+            .get_physical_device_surface_support(pdevice, index, self.surface)
+            .unwrap_or(false);
+
+        // ash wants a `&mut vk::Display` here, but the function actually takes the `Display*`
+        // itself
+        surface_support
+            && self.xlib_surface_loader.get_physical_device_xlib_presentation_support(
+                pdevice,
+                index,
+                &mut *(display as *mut vk::Display),
+                visual_id,
+            )
+    }
+}
+
+impl Drop for InstanceState {
+    fn drop(&mut self) {
+        unsafe {
+            if self.surface != vk::SurfaceKHR::null() {
+                self.surface_loader.destroy_surface(self.surface, None);
+            }
+            if !self.shared {
+                self.debug_messenger = None;
+                self.instance.destroy_instance(None);
+            }
+        }
+    }
+}
+
+pub struct Init {
     device: Device,
-    // command_pool: vk::CommandPool,
+    pdevice: vk::PhysicalDevice,
+    queues: VkQueues,
+    // This is dropped after the device has been destroyed in `Init`'s `Drop` implementation
+    instance_state: InstanceState,
 }
 
 pub struct VkContext {
     window: other_c_ulong,
     display: *mut xlib::_XDisplay,
+    // This needs to be dropped before the device
+    swapchain: Option<VkSwapchain>,
     pub init: Init,
 }
 
 impl VkContext {
+    /// `size` is the window's current physical size, which is used to size the swapchain.
+    /// `transparent` should be set if the window was created with a 32-bit ARGB visual.
     pub unsafe fn create(
-        window: other_c_ulong, display: *mut xlib::_XDisplay,
+        window: other_c_ulong, display: *mut xlib::_XDisplay, config: VkConfig, size: PhySize,
+        transparent: bool,
     ) -> Result<VkContext, VkError> {
         if display.is_null() {
             return Err(VkError::InvalidWindowHandle);
         }
 
-        Ok(VkContext { window, display, init: Init::new(window, display).unwrap() })
+        let init = Init::new(window, display, &config)?;
+        let swapchain = match config.swapchain {
+            Some(swapchain_config) => Some(VkSwapchain::new(
+                &init.instance_state.entry,
+                &init.instance_state.instance,
+                &init.device,
+                init.pdevice,
+                init.instance_state.surface,
+                swapchain_config,
+                size,
+                transparent,
+            )?),
+            None => None,
+        };
+
+        Ok(VkContext { window, display, swapchain, init })
     }
 
     pub fn get_device(&self) -> &Device {
         &self.init.device
     }
+
+    pub fn entry(&self) -> &Entry {
+        &self.init.instance_state.entry
+    }
+
+    pub fn instance(&self) -> &Instance {
+        &self.init.instance_state.instance
+    }
+
+    pub fn physical_device(&self) -> vk::PhysicalDevice {
+        self.init.pdevice
+    }
+
+    pub fn surface(&self) -> vk::SurfaceKHR {
+        self.init.instance_state.surface
+    }
+
+    pub fn queues(&self) -> VkQueues {
+        self.init.queues
+    }
+
+    pub fn swapchain(&self) -> Option<&VkSwapchain> {
+        self.swapchain.as_ref()
+    }
+
+    pub fn swapchain_mut(&mut self) -> Option<&mut VkSwapchain> {
+        self.swapchain.as_mut()
+    }
 }
 
 impl Drop for VkContext {
     fn drop(&mut self) {}
 }
 
-fn check_physical_device_surface_support(
-    pdevice: vk::PhysicalDevice, queue_family_index: u32, surface: &XlibSurface,
-    display: *mut vk::Display, visual_id: vk::VisualID,
-) -> bool {
-    unsafe {
-        surface.get_physical_device_xlib_presentation_support(
-            pdevice,
-            queue_family_index,
-            &mut (display as *const c_void),
-            visual_id,
-        )
+/// The ID of the visual `window` was created with.
+unsafe fn window_visual_id(window: other_c_ulong, display: *mut xlib::_XDisplay) -> vk::VisualID {
+    let mut attributes: xlib::XWindowAttributes = std::mem::zeroed();
+    xlib::XGetWindowAttributes(display, window, &mut attributes);
+
+    xlib::XVisualIDFromVisual(attributes.visual) as vk::VisualID
+}
+
+/// How well a physical device of type `device_type` matches the selector, lower is better.
+fn device_type_rank(selector: &VkDeviceSelector, device_type: vk::PhysicalDeviceType) -> u32 {
+    let preferred = match selector {
+        VkDeviceSelector::PreferIntegrated => {
+            [vk::PhysicalDeviceType::INTEGRATED_GPU, vk::PhysicalDeviceType::DISCRETE_GPU]
+        }
+        _ => [vk::PhysicalDeviceType::DISCRETE_GPU, vk::PhysicalDeviceType::INTEGRATED_GPU],
+    };
+
+    match device_type {
+        device_type if device_type == preferred[0] => 0,
+        device_type if device_type == preferred[1] => 1,
+        vk::PhysicalDeviceType::VIRTUAL_GPU => 2,
+        vk::PhysicalDeviceType::CPU => 3,
+        _ => 4,
     }
 }
 
+/// The `deviceUUID` of `pdevice`. This requires Vulkan 1.1.
+unsafe fn device_uuid(instance: &Instance, pdevice: vk::PhysicalDevice) -> [u8; vk::UUID_SIZE] {
+    let mut id_properties = vk::PhysicalDeviceIDProperties::default();
+    let mut properties = vk::PhysicalDeviceProperties2::builder().push_next(&mut id_properties);
+    instance.get_physical_device_properties2(pdevice, &mut properties);
+
+    id_properties.device_uuid
+}
+
 impl Init {
-    pub fn new(window: other_c_ulong, display: *mut xlib::_XDisplay) -> Result<Self, String> {
+    pub fn new(
+        window: other_c_ulong, display: *mut xlib::_XDisplay, config: &VkConfig,
+    ) -> Result<Self, VkError> {
+        if let Some(shared_device) = &config.shared_device {
+            return unsafe { Self::from_shared_device(window, display, shared_device) };
+        }
+
         unsafe {
             let entry = Entry::linked();
 
-            // TODO pass in config
-            let app_name = CString::new("Eyecatcher").unwrap();
+            let api_version = vk::make_api_version(
+                0,
+                config.minimum_version.0 as u32,
+                config.minimum_version.1 as u32,
+                0,
+            );
+            let instance_version =
+                entry.try_enumerate_instance_version()?.unwrap_or(vk::API_VERSION_1_0);
+            if instance_version < api_version {
+                return Err(VkError::VersionNotSupported);
+            }
+
+            // Interior nul bytes would make for an invalid name, so we'll just leave it empty
+            let app_name = CString::new(config.application_name.as_str()).unwrap_or_default();
+            let engine_name = CString::new("baseview").unwrap();
 
             let app_info = vk::ApplicationInfo::builder()
                 .application_name(&app_name)
-                .application_version(vk::make_api_version(1, 3, 0, 0))
-                .engine_version(vk::make_api_version(1, 0, 0, 0))
-                .api_version(vk::API_VERSION_1_0)
-                .engine_name(&app_name);
+                .engine_name(&engine_name)
+                .api_version(api_version);
+
+            let mut layer_names = Vec::new();
+            if config.request_validation_layers {
+                let validation_layer = CString::new(VALIDATION_LAYER).unwrap();
+                let available_layers = entry.enumerate_instance_layer_properties()?;
+                if !available_layers
+                    .iter()
+                    .any(|layer| CStr::from_ptr(layer.layer_name.as_ptr()) == &*validation_layer)
+                {
+                    return Err(VkError::LayerNotPresent(String::from(VALIDATION_LAYER)));
+                }
+
+                layer_names.push(validation_layer);
+            }
+
+            let available_extensions: Vec<CString> = entry
+                .enumerate_instance_extension_properties(None)?
+                .iter()
+                .map(|extension| CStr::from_ptr(extension.extension_name.as_ptr()).to_owned())
+                .collect();
+
+            let mut extension_names =
+                vec![Surface::name().to_owned(), XlibSurface::name().to_owned()];
+            for extension in &config.instance_extensions {
+                let extension = CString::new(extension.as_str())
+                    .map_err(|_| VkError::InvalidName(extension.clone()))?;
+                if !extension_names.contains(&extension) {
+                    extension_names.push(extension);
+                }
+            }
+            if let Some(missing) =
+                extension_names.iter().find(|extension| !available_extensions.contains(extension))
+            {
+                return Err(VkError::ExtensionNotPresent(missing.to_string_lossy().into_owned()));
+            }
+
+            // The debug messenger is a convenience, so it's fine if it's not available
+            let debug_utils_name = DebugUtils::name().to_owned();
+            let use_debug_messenger = config.use_default_debug_messenger
+                && available_extensions.contains(&debug_utils_name);
+            if use_debug_messenger && !extension_names.contains(&debug_utils_name) {
+                extension_names.push(debug_utils_name);
+            }
 
-            let extension_names_raw = [
-                XlibSurface::name().as_ptr(),
-                DebugUtils::name().as_ptr(),
-                Surface::name().as_ptr(),
-            ];
+            let layer_names_raw: Vec<_> = layer_names.iter().map(|name| name.as_ptr()).collect();
+            let extension_names_raw: Vec<_> =
+                extension_names.iter().map(|name| name.as_ptr()).collect();
 
             let instance_info = vk::InstanceCreateInfo::builder()
                 .application_info(&app_info)
+                .enabled_layer_names(&layer_names_raw)
                 .enabled_extension_names(&extension_names_raw);
 
-            let instance = entry
-                .create_instance(&instance_info, None)
-                .map_err(|_| "Unable to create Vulkan instance.")?;
+            let instance = entry.create_instance(&instance_info, None)?;
+            let mut instance_state = InstanceState::new(entry, instance, false);
 
-            let surface_info = vk::XlibSurfaceCreateInfoKHR::builder()
-                .window(window)
-                .dpy(display as *mut _)
-                .build();
-
-            let surface_loader = Surface::new(&entry, &instance);
-            let surface_fn = XlibSurface::new(&entry, &instance);
-
-            let surface = surface_fn
-                .create_xlib_surface(&surface_info, None)
-                .map_err(|_| "Unable to create Vulkan Xlib surface.")?;
+            if use_debug_messenger {
+                instance_state.debug_messenger = Some(DebugMessenger::new(
+                    &instance_state.entry,
+                    &instance_state.instance,
+                    config.debug_callback.clone(),
+                )?);
+            }
 
-            let pdevices = instance.enumerate_physical_devices().expect("Physical device error");
+            instance_state.create_surface(window, display)?;
+            let instance = &instance_state.instance;
+            let visual_id = window_visual_id(window, display);
 
-            let (pdevice, queue_family_index) = pdevices
+            let mut device_extension_names = config
+                .required_device_extensions
                 .iter()
-                .find_map(|pdevice| {
-                    instance
-                        .get_physical_device_queue_family_properties(*pdevice)
+                .map(|extension| {
+                    CString::new(extension.as_str())
+                        .map_err(|_| VkError::InvalidName(extension.clone()))
+                })
+                .collect::<Result<Vec<_>, _>>()?;
+            let swapchain_name = Swapchain::name().to_owned();
+            if config.swapchain.is_some() && !device_extension_names.contains(&swapchain_name) {
+                device_extension_names.push(swapchain_name);
+            }
+
+            let candidates: Vec<_> = instance
+                .enumerate_physical_devices()?
+                .into_iter()
+                .filter_map(|pdevice| {
+                    let properties = instance.get_physical_device_properties(pdevice);
+                    if properties.api_version < api_version {
+                        return None;
+                    }
+
+                    let supported_extensions: Vec<CString> = instance
+                        .enumerate_device_extension_properties(pdevice)
+                        .ok()?
                         .iter()
-                        .enumerate()
-                        .find_map(|(index, info)| {
-                            let supports_graphic =
-                                info.queue_flags.contains(vk::QueueFlags::GRAPHICS);
-                            // TOOD X11 presentation support
-                            // let support_presentation = unsafe {
-                            //     surface_fn.get_physical_device_xlib_presentation_support(
-                            //         pdevices[0],
-                            //         0,
-                            //         &mut (display as *const c_void),
-                            //         visual_id,
-                            //     )
-                            // };
-                            let supports_surface = surface_loader
-                                .get_physical_device_surface_support(
-                                    *pdevice,
-                                    index as u32,
-                                    surface,
-                                ).unwrap();
-                            if supports_graphic && supports_surface {
-                                Some((*pdevice, index))
-                            } else {
-                                None
-                            }
+                        .map(|extension| {
+                            CStr::from_ptr(extension.extension_name.as_ptr()).to_owned()
                         })
+                        .collect();
+                    if !device_extension_names
+                        .iter()
+                        .all(|extension| supported_extensions.contains(extension))
+                    {
+                        return None;
+                    }
+
+                    QueueFamilies::find(
+                        instance,
+                        pdevice,
+                        |index| instance_state.supports_present(pdevice, index, display, visual_id),
+                        config.required_dedicated_transfer_queue,
+                    )
+                    .map(|queue_families| (pdevice, properties, queue_families))
                 })
-                .expect("Couldn't find suitable device.");
-            let queue_family_index = queue_family_index as u32;
-
-            let device_extension_names_raw = [
-                Swapchain::name().as_ptr(),
-                #[cfg(any(target_os = "macos", target_os = "ios"))]
-                KhrPortabilitySubsetFn::NAME.as_ptr(),
-            ];
+                .collect();
+
+            // `min_by_key()` returns the first device in case of a tie, so the driver's ordering
+            // is kept for devices of the same type
+            let (pdevice, _, queue_families) = match &config.device_selector {
+                selector @ (VkDeviceSelector::PreferDiscrete
+                | VkDeviceSelector::PreferIntegrated) => {
+                    candidates.into_iter().min_by_key(|(_, properties, _)| {
+                        device_type_rank(selector, properties.device_type)
+                    })
+                }
+                VkDeviceSelector::ByName(name) => {
+                    let name = name.to_lowercase();
+                    candidates.into_iter().find(|(_, properties, _)| {
+                        CStr::from_ptr(properties.device_name.as_ptr())
+                            .to_string_lossy()
+                            .to_lowercase()
+                            .contains(&name)
+                    })
+                }
+                VkDeviceSelector::ByUuid(uuid) => {
+                    if api_version < vk::API_VERSION_1_1 {
+                        return Err(VkError::VersionNotSupported);
+                    }
+
+                    candidates
+                        .into_iter()
+                        .find(|(pdevice, _, _)| &device_uuid(instance, *pdevice) == uuid)
+                }
+            }
+            .ok_or(VkError::NoSuitableDevice)?;
+
+            let device_extension_names_raw: Vec<_> =
+                device_extension_names.iter().map(|name| name.as_ptr()).collect();
             let features =
                 vk::PhysicalDeviceFeatures { shader_clip_distance: 1, ..Default::default() };
             let priorities = [1.0];
 
-            let queue_info = vk::DeviceQueueCreateInfo::builder()
-                .queue_family_index(queue_family_index)
-                .queue_priorities(&priorities);
+            let queue_infos: Vec<_> = queue_families
+                .unique_indices()
+                .into_iter()
+                .map(|queue_family_index| {
+                    vk::DeviceQueueCreateInfo::builder()
+                        .queue_family_index(queue_family_index)
+                        .queue_priorities(&priorities)
+                        .build()
+                })
+                .collect();
 
             let device_create_info = vk::DeviceCreateInfo::builder()
-                .queue_create_infos(std::slice::from_ref(&queue_info))
+                .queue_create_infos(&queue_infos)
                 .enabled_extension_names(&device_extension_names_raw)
                 .enabled_features(&features);
 
-            let device: Device =
-                instance.create_device(pdevice, &device_create_info, None).unwrap();
+            let device: Device = instance.create_device(pdevice, &device_create_info, None)?;
 
-            let present_queue = device.get_device_queue(queue_family_index, 0);
-
-            let surface_formats = surface_loader
-                .get_physical_device_surface_formats(pdevice, surface)
-                .expect("Surface formats error");
-
-            // Print surfaces
-            for surface_format in surface_formats.iter() {
-                println!("Surface format: {:?}", surface_format);
-            }
+            // We only ever create a single queue per family
+            let get_queue = |family_index: u32| VkQueue {
+                queue: device.get_device_queue(family_index, 0),
+                family_index,
+            };
+            let queues = VkQueues {
+                graphics: get_queue(queue_families.graphics),
+                present: get_queue(queue_families.present),
+                transfer: queue_families.transfer.map(get_queue),
+            };
 
-            // let surface_formats = surface_fn
-            //     .get_physical_device_surface_formats_khr(pdevice, surface)
-            //     .expect("Surface formats error");
+            Ok(Self { device, pdevice, queues, instance_state })
+        }
+    }
 
-            Ok(Self { entry, instance, surface, pdevice, queue_family_index, device })
+    /// Only create a surface for the window, and use the instance and device from the
+    /// [VkSharedDevice] for everything else.
+    unsafe fn from_shared_device(
+        window: other_c_ulong, display: *mut xlib::_XDisplay, shared_device: &VkSharedDevice,
+    ) -> Result<Self, VkError> {
+        let mut instance_state =
+            InstanceState::new(shared_device.entry.clone(), shared_device.instance.clone(), true);
+        instance_state.create_surface(window, display)?;
+
+        let visual_id = window_visual_id(window, display);
+        if !instance_state.supports_present(
+            shared_device.physical_device,
+            shared_device.queues.present.family_index,
+            display,
+            visual_id,
+        ) {
+            return Err(VkError::NoSuitableDevice);
         }
+
+        Ok(Self {
+            device: shared_device.device.clone(),
+            pdevice: shared_device.physical_device,
+            queues: shared_device.queues,
+            instance_state,
+        })
     }
 
     // pub unsafe fn make_current(&self) {
@@ -229,10 +629,15 @@ impl Init {
 
 impl Drop for Init {
     fn drop(&mut self) {
+        if self.instance_state.shared {
+            return;
+        }
+
         unsafe {
-            // TODO
-            //self.instance.destroy_surface_khr(self.surface, None);
-            //self.instance.destroy_instance(None);
+            // Errors here would mean the device was lost, in which case there's nothing left to
+            // wait for
+            let _ = self.device.device_wait_idle();
+            self.device.destroy_device(None);
         }
     }
 }
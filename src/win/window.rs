@@ -1,21 +1,21 @@
 use winapi::shared::guiddef::GUID;
 use winapi::shared::minwindef::{ATOM, FALSE, LPARAM, LRESULT, UINT, WPARAM};
-use winapi::shared::windef::{HWND, RECT};
+use winapi::shared::windef::{HWND, POINT, RECT};
 use winapi::um::combaseapi::CoCreateGuid;
 use winapi::um::ole2::{OleInitialize, RegisterDragDrop, RevokeDragDrop};
 use winapi::um::oleidl::LPDROPTARGET;
 use winapi::um::winuser::{
     AdjustWindowRectEx, CreateWindowExW, DefWindowProcW, DestroyWindow, DispatchMessageW,
-    GetDpiForWindow, GetMessageW, GetWindowLongPtrW, LoadCursorW, PostMessageW, RegisterClassW,
-    ReleaseCapture, SetCapture, SetProcessDpiAwarenessContext, SetTimer, SetWindowLongPtrW,
-    SetWindowPos, TranslateMessage, UnregisterClassW, CS_OWNDC, GET_XBUTTON_WPARAM, GWLP_USERDATA,
-    IDC_ARROW, MSG, SWP_NOMOVE, SWP_NOZORDER, WHEEL_DELTA, WM_CHAR, WM_CLOSE, WM_CREATE,
-    WM_DPICHANGED, WM_INPUTLANGCHANGE, WM_KEYDOWN, WM_KEYUP, WM_LBUTTONDOWN, WM_LBUTTONUP,
-    WM_MBUTTONDOWN, WM_MBUTTONUP, WM_MOUSEHWHEEL, WM_MOUSEMOVE, WM_MOUSEWHEEL, WM_NCDESTROY,
-    WM_RBUTTONDOWN, WM_RBUTTONUP, WM_SHOWWINDOW, WM_SIZE, WM_SYSCHAR, WM_SYSKEYDOWN, WM_SYSKEYUP,
-    WM_TIMER, WM_USER, WM_XBUTTONDOWN, WM_XBUTTONUP, WNDCLASSW, WS_CAPTION, WS_CHILD,
-    WS_CLIPSIBLINGS, WS_MAXIMIZEBOX, WS_MINIMIZEBOX, WS_POPUPWINDOW, WS_SIZEBOX, WS_VISIBLE,
-    XBUTTON1, XBUTTON2,
+    GetDpiForWindow, GetFocus, GetMessageW, GetParent, GetWindowLongPtrW, GetWindowRect,
+    LoadCursorW, PostMessageW, RegisterClassW, ReleaseCapture, ScreenToClient, SetCapture,
+    SetProcessDpiAwarenessContext, SetTimer, SetWindowLongPtrW, SetWindowPos, TranslateMessage,
+    UnregisterClassW, CS_OWNDC, GET_XBUTTON_WPARAM, GWLP_USERDATA, IDC_ARROW, MSG, SWP_NOACTIVATE,
+    SWP_NOMOVE, SWP_NOSIZE, SWP_NOZORDER, WHEEL_DELTA, WM_CHAR, WM_CLOSE, WM_CREATE, WM_DPICHANGED,
+    WM_INPUTLANGCHANGE, WM_KEYDOWN, WM_KEYUP, WM_LBUTTONDOWN, WM_LBUTTONUP, WM_MBUTTONDOWN,
+    WM_MBUTTONUP, WM_MOUSEHWHEEL, WM_MOUSEMOVE, WM_MOUSEWHEEL, WM_NCDESTROY, WM_RBUTTONDOWN,
+    WM_RBUTTONUP, WM_SHOWWINDOW, WM_SIZE, WM_SYSCHAR, WM_SYSKEYDOWN, WM_SYSKEYUP, WM_TIMER,
+    WM_USER, WM_XBUTTONDOWN, WM_XBUTTONUP, WNDCLASSW, WS_CAPTION, WS_CHILD, WS_CLIPSIBLINGS,
+    WS_MAXIMIZEBOX, WS_MINIMIZEBOX, WS_POPUPWINDOW, WS_SIZEBOX, WS_VISIBLE, XBUTTON1, XBUTTON2,
 };
 
 use std::cell::{Cell, Ref, RefCell, RefMut};
@@ -33,8 +33,9 @@ use raw_window_handle::{
 const BV_WINDOW_MUST_CLOSE: UINT = WM_USER + 1;
 
 use crate::{
-    Event, MouseButton, MouseCursor, MouseEvent, PhyPoint, PhySize, ScrollDelta, Size, WindowEvent,
-    WindowHandler, WindowInfo, WindowOpenOptions, WindowScalePolicy,
+    Event, MonitorInfo, MouseButton, MouseCursor, MouseEvent, PhyPoint, PhySize, ScaleSource,
+    ScrollDelta, Size, WindowEvent, WindowHandler, WindowInfo, WindowOpenOptions,
+    WindowScalePolicy,
 };
 
 use super::drop_target::DropTarget;
@@ -355,10 +356,10 @@ unsafe fn wnd_proc_inner(
         WM_DPICHANGED => {
             // To avoid weirdness with the realtime borrow checker.
             let new_rect = {
-                if let WindowScalePolicy::SystemScaleFactor = window_state.scale_policy {
-                    let dpi = (wparam & 0xFFFF) as u16 as u32;
-                    let scale_factor = dpi as f64 / 96.0;
-
+                let dpi = (wparam & 0xFFFF) as u16 as u32;
+                if let Some(scale_factor) =
+                    window_state.scale_policy.apply_to_system_scale(dpi as f64 / 96.0)
+                {
                     let mut window_info = window_state.window_info.borrow_mut();
                     *window_info =
                         WindowInfo::from_logical_size(window_info.logical_size(), scale_factor);
@@ -515,6 +516,26 @@ impl WindowState {
                     )
                 };
             }
+            WindowTask::Move(position) => {
+                let mut point = POINT { x: position.x, y: position.y };
+                unsafe {
+                    // Child windows are positioned relative to their parent's client area
+                    let parent = GetParent(self.hwnd);
+                    if !parent.is_null() {
+                        ScreenToClient(parent, &mut point);
+                    }
+
+                    SetWindowPos(
+                        self.hwnd,
+                        null_mut(),
+                        point.x,
+                        point.y,
+                        0,
+                        0,
+                        SWP_NOZORDER | SWP_NOSIZE | SWP_NOACTIVATE,
+                    )
+                };
+            }
         }
     }
 }
@@ -526,6 +547,8 @@ pub(super) enum WindowTask {
     /// Resize the window to the given size. The size is in logical pixels. DPI scaling is applied
     /// automatically.
     Resize(Size),
+    /// Move the window's frame to the given position in physical desktop coordinates.
+    Move(PhyPoint),
 }
 
 pub struct Window<'a> {
@@ -590,7 +613,7 @@ impl Window<'_> {
             // todo: manage error ^
 
             let scaling = match options.scale {
-                WindowScalePolicy::SystemScaleFactor => 1.0,
+                WindowScalePolicy::SystemScaleFactor | WindowScalePolicy::Auto(_) => 1.0,
                 WindowScalePolicy::ScaleFactor(scale) => scale,
             };
 
@@ -681,11 +704,11 @@ impl Window<'_> {
             );
 
             // Now we can get the actual dpi of the window.
-            let new_rect = if let WindowScalePolicy::SystemScaleFactor = options.scale {
-                // Only works on Windows 10 unfortunately.
-                let dpi = GetDpiForWindow(hwnd);
-                let scale_factor = dpi as f64 / 96.0;
-
+            // Only works on Windows 10 unfortunately.
+            let dpi = GetDpiForWindow(hwnd);
+            let new_rect = if let Some(scale_factor) =
+                options.scale.apply_to_system_scale(dpi as f64 / 96.0)
+            {
                 let mut window_info = window_state.window_info.borrow_mut();
                 if window_info.scale() != scale_factor {
                     *window_info =
@@ -749,6 +772,44 @@ impl Window<'_> {
         self.state.deferred_tasks.borrow_mut().push_back(task);
     }
 
+    pub fn window_info(&self) -> WindowInfo {
+        *self.state.window_info()
+    }
+
+    pub fn outer_position(&self) -> PhyPoint {
+        let mut rect = RECT { left: 0, top: 0, right: 0, bottom: 0 };
+        unsafe { GetWindowRect(self.state.hwnd, &mut rect) };
+
+        PhyPoint::new(rect.left, rect.top)
+    }
+
+    pub fn set_outer_position(&mut self, position: PhyPoint) {
+        // Moving the window sends `WM_MOVE` to `wnd_proc`, so just like resizing this needs to be
+        // deferred
+        let task = WindowTask::Move(position);
+        self.state.deferred_tasks.borrow_mut().push_back(task);
+    }
+
+    pub fn has_focus(&self) -> bool {
+        unsafe { GetFocus() == self.state.hwnd }
+    }
+
+    pub fn scale_factor(&self) -> f64 {
+        self.state.window_info().scale()
+    }
+
+    pub fn scale_source(&self) -> Option<ScaleSource> {
+        match self.state.scale_policy {
+            WindowScalePolicy::ScaleFactor(_) => None,
+            _ => Some(ScaleSource::System),
+        }
+    }
+
+    pub fn current_monitor(&self) -> Option<MonitorInfo> {
+        // Monitor enumeration is not implemented on this platform yet
+        None
+    }
+
     pub fn set_mouse_cursor(&mut self, _mouse_cursor: MouseCursor) {
         todo!()
     }
@@ -777,3 +838,8 @@ unsafe impl HasRawDisplayHandle for Window<'_> {
 pub fn copy_to_clipboard(_data: &str) {
     todo!()
 }
+
+pub fn monitors() -> Vec<MonitorInfo> {
+    // Monitor enumeration is not implemented on this platform yet
+    Vec::new()
+}
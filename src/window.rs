@@ -1,4 +1,8 @@
 use std::marker::PhantomData;
+#[cfg(target_os = "linux")]
+use std::os::unix::io::{AsRawFd, RawFd};
+#[cfg(target_os = "linux")]
+use std::time::Duration;
 
 use raw_window_handle::{
     HasRawDisplayHandle, HasRawWindowHandle, RawDisplayHandle, RawWindowHandle,
@@ -6,7 +10,9 @@ use raw_window_handle::{
 
 use crate::event::{Event, EventStatus};
 use crate::window_open_options::WindowOpenOptions;
-use crate::{MouseCursor, Size};
+#[cfg(target_os = "linux")]
+use crate::{CursorGrabMode, PhyRect, PhySize, Point};
+use crate::{MonitorInfo, MouseCursor, PhyPoint, ScaleSource, Size, WindowInfo};
 
 #[cfg(target_os = "macos")]
 use crate::macos as platform;
@@ -44,6 +50,118 @@ unsafe impl HasRawWindowHandle for WindowHandle {
     }
 }
 
+/// A parented window that runs on the thread that opened it, created with
+/// [Window::open_parented_embedded]. Instead of running its own event loop, the window is driven
+/// by the host's event loop: [Self::pump] should be called whenever the file descriptor returned
+/// by [AsRawFd::as_raw_fd] becomes readable, and [Self::on_timer] should be called periodically at
+/// [Self::frame_interval]. Dropping this closes the window.
+///
+/// This matches the model used by the CLAP `posix-fd-support` and `timer-support` extensions.
+#[cfg(target_os = "linux")]
+pub struct EmbeddedWindow {
+    window: platform::EmbeddedWindow,
+    // so that EmbeddedWindow is !Send on all platforms
+    phantom: PhantomData<*mut ()>,
+}
+
+#[cfg(target_os = "linux")]
+impl EmbeddedWindow {
+    /// Process all pending events from the windowing system.
+    pub fn pump(&mut self) {
+        self.window.pump();
+    }
+
+    /// Call [WindowHandler::on_frame], and process any pending events.
+    pub fn on_timer(&mut self) {
+        self.window.on_timer();
+    }
+
+    /// The interval [Self::on_timer] should be called at.
+    pub fn frame_interval(&self) -> Duration {
+        self.window.frame_interval()
+    }
+
+    /// The time until the next timer set with [Window::set_timer] is due. [Self::pump] should be
+    /// called once it has passed, even if the file descriptor did not become readable. Returns
+    /// `None` if there are no timers or if the window has been closed.
+    pub fn next_timeout(&self) -> Option<Duration> {
+        self.window.next_timeout()
+    }
+
+    /// Returns `false` once the window has been closed, for instance because the window handler
+    /// called [Window::close]. The host should then drop the window.
+    pub fn is_open(&self) -> bool {
+        self.window.is_open()
+    }
+}
+
+#[cfg(target_os = "linux")]
+impl AsRawFd for EmbeddedWindow {
+    fn as_raw_fd(&self) -> RawFd {
+        self.window.as_raw_fd()
+    }
+}
+
+#[cfg(target_os = "linux")]
+unsafe impl HasRawWindowHandle for EmbeddedWindow {
+    fn raw_window_handle(&self) -> RawWindowHandle {
+        self.window.raw_window_handle()
+    }
+}
+
+/// A top level window that runs on the thread that opened it, created with
+/// [Window::open_with_event_loop]. Unlike [Window::open_blocking], the event loop is not run
+/// internally. Instead [Self::pump_events] runs a single iteration of it, which makes it possible to
+/// integrate the window with another event loop like calloop, mio, or tokio by registering the file
+/// descriptor returned by [AsRawFd::as_raw_fd]. Dropping this closes the window.
+#[cfg(target_os = "linux")]
+pub struct EventLoop {
+    event_loop: platform::EventLoop,
+    // so that EventLoop is !Send on all platforms
+    phantom: PhantomData<*mut ()>,
+}
+
+/// Returned by [EventLoop::pump_events].
+#[cfg(target_os = "linux")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PumpStatus {
+    /// The window is still open.
+    Continue,
+    /// The window has been closed. The event loop should be dropped.
+    Exit,
+}
+
+#[cfg(target_os = "linux")]
+impl EventLoop {
+    /// Process pending events and draw a frame if one is due. If there are no pending events this
+    /// waits until the next frame is due, or until `timeout` has passed if that happens sooner. Use
+    /// a timeout of [Duration::ZERO] to never block.
+    pub fn pump_events(&mut self, timeout: Option<Duration>) -> PumpStatus {
+        self.event_loop.pump_events(timeout)
+    }
+
+    /// The time until the next frame or timer is due, after which [Self::pump_events] should be
+    /// called even if the file descriptor did not become readable. Returns `None` once the window
+    /// has been closed.
+    pub fn next_timeout(&self) -> Option<Duration> {
+        self.event_loop.next_timeout()
+    }
+}
+
+#[cfg(target_os = "linux")]
+impl AsRawFd for EventLoop {
+    fn as_raw_fd(&self) -> RawFd {
+        self.event_loop.as_raw_fd()
+    }
+}
+
+#[cfg(target_os = "linux")]
+unsafe impl HasRawWindowHandle for EventLoop {
+    fn raw_window_handle(&self) -> RawWindowHandle {
+        self.event_loop.raw_window_handle()
+    }
+}
+
 pub trait WindowHandler {
     fn on_frame(&mut self, window: &mut Window);
     fn on_event(&mut self, window: &mut Window, event: Event) -> EventStatus;
@@ -78,6 +196,33 @@ impl<'a> Window<'a> {
         WindowHandle::new(window_handle)
     }
 
+    /// Like [Self::open_parented], but the window is created on the current thread and driven by
+    /// the caller's event loop instead of getting its own thread. See [EmbeddedWindow].
+    #[cfg(target_os = "linux")]
+    pub fn open_parented_embedded<P, H, B>(
+        parent: &P, options: WindowOpenOptions, build: B,
+    ) -> EmbeddedWindow
+    where
+        P: HasRawWindowHandle,
+        H: WindowHandler + 'static,
+        B: FnOnce(&mut Window) -> H,
+    {
+        let window = platform::Window::open_parented_embedded::<P, H, B>(parent, options, build);
+        EmbeddedWindow { window, phantom: PhantomData }
+    }
+
+    /// Like [Self::open_blocking], but instead of running the event loop until the window is closed
+    /// this returns an [EventLoop] that can be driven by the caller.
+    #[cfg(target_os = "linux")]
+    pub fn open_with_event_loop<H, B>(options: WindowOpenOptions, build: B) -> EventLoop
+    where
+        H: WindowHandler + 'static,
+        B: FnOnce(&mut Window) -> H,
+    {
+        let event_loop = platform::Window::open_with_event_loop::<H, B>(options, build);
+        EventLoop { event_loop, phantom: PhantomData }
+    }
+
     pub fn open_blocking<H, B>(options: WindowOpenOptions, build: B)
     where
         H: WindowHandler + 'static,
@@ -98,22 +243,145 @@ impl<'a> Window<'a> {
         self.window.resize(size);
     }
 
+    /// The window's current size and scale factor.
+    pub fn window_info(&self) -> WindowInfo {
+        self.window.window_info()
+    }
+
+    /// The position of the top left corner of the window's frame (including any decorations drawn
+    /// by the window manager) in physical desktop coordinates.
+    pub fn outer_position(&self) -> PhyPoint {
+        self.window.outer_position()
+    }
+
+    /// Move the window so its frame's top left corner ends up at `position`, in physical desktop
+    /// coordinates. The window handler will receive a [crate::WindowEvent::Moved] event once the
+    /// window has actually moved. The window manager may not honor this request.
+    pub fn set_outer_position(&mut self, position: PhyPoint) {
+        self.window.set_outer_position(position);
+    }
+
+    /// Start a timer that sends an [Event::Timer] event with `id` to the window handler after
+    /// `interval` has passed, and then every `interval` if `repeat` is set. Setting a timer with
+    /// the same ID as an existing timer replaces that timer. Timers are only checked once per
+    /// iteration of the event loop, so they may fire slightly late.
+    ///
+    /// Timers are currently only supported on Linux.
+    #[cfg(target_os = "linux")]
+    pub fn set_timer(&mut self, id: u32, interval: Duration, repeat: bool) {
+        self.window.set_timer(id, interval, repeat);
+    }
+
+    /// Stop the timer set with [Self::set_timer]. Does nothing if there's no timer with this ID.
+    #[cfg(target_os = "linux")]
+    pub fn cancel_timer(&mut self, id: u32) {
+        self.window.cancel_timer(id);
+    }
+
+    /// Whether the window currently has keyboard focus.
+    pub fn has_focus(&self) -> bool {
+        self.window.has_focus()
+    }
+
+    /// The window's current scale factor. This is the same scale factor as the one in the
+    /// [crate::WindowInfo] passed with the last [crate::WindowEvent::Resized] event.
+    pub fn scale_factor(&self) -> f64 {
+        self.window.scale_factor()
+    }
+
+    /// Where the system scale factor was read from, or `None` if the window uses a fixed scale
+    /// factor or none of the sources had a value. See [crate::WindowScalePolicy::Auto].
+    pub fn scale_source(&self) -> Option<ScaleSource> {
+        self.window.scale_source()
+    }
+
+    /// The monitor the center of the window is currently on, if any. Always `None` on Windows and
+    /// macOS for now, see [crate::monitors].
+    pub fn current_monitor(&self) -> Option<MonitorInfo> {
+        self.window.current_monitor()
+    }
+
+    /// Confine or lock the mouse cursor, see [CursorGrabMode]. Returns `false` if the cursor could
+    /// not be grabbed, for instance because another application has already grabbed it or because
+    /// the window is not visible. This is currently only supported on Linux.
+    #[cfg(target_os = "linux")]
+    pub fn set_cursor_grab(&mut self, mode: CursorGrabMode) -> bool {
+        self.window.set_cursor_grab(mode)
+    }
+
+    /// Move the mouse cursor to `position`, in logical coordinates relative to the window. While
+    /// the cursor is locked, this instead changes where the cursor will reappear when it is
+    /// unlocked. This is currently only supported on Linux.
+    #[cfg(target_os = "linux")]
+    pub fn set_cursor_position(&mut self, position: Point) {
+        self.window.set_cursor_position(position);
+    }
+
+    /// Send all mouse events to this window, even when the cursor is outside of the window, until
+    /// [Self::release_mouse] is called. This is useful while dragging a control, so the button
+    /// release is not lost. If the capture ends for any other reason a
+    /// [crate::MouseEvent::CaptureLost] event is sent. Returns `false` if the mouse could not be
+    /// captured. This is currently only supported on Linux.
+    #[cfg(target_os = "linux")]
+    pub fn capture_mouse(&mut self) -> bool {
+        self.window.capture_mouse()
+    }
+
+    /// End the mouse capture started with [Self::capture_mouse].
+    #[cfg(target_os = "linux")]
+    pub fn release_mouse(&mut self) {
+        self.window.release_mouse();
+    }
+
     pub fn set_mouse_cursor(&mut self, cursor: MouseCursor) {
         self.window.set_mouse_cursor(cursor);
     }
 
+    /// Draw CPU rendered pixels to the window without going through OpenGL or Vulkan. `pixels`
+    /// contains `size.width * size.height` pixels in `0x00RRGGBB` format, stored row by row. The
+    /// upper 8 bits of each pixel are ignored, unless the window was opened with
+    /// [WindowOpenOptions::transparent] in which case the pixels are in premultiplied `0xAARRGGBB`
+    /// format. Only the regions of the buffer covered by `damage` are updated, or the entire buffer
+    /// if `damage` is empty. The buffer is drawn to the window's top left corner.
+    ///
+    /// This should not be combined with an OpenGL or Vulkan context for the same window. This is
+    /// currently only supported on Linux, and nothing is drawn if the window's X11 visual is not a
+    /// TrueColor visual with 16, 24, or 32 bits per pixel.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `pixels` contains fewer than `size.width * size.height` pixels.
+    #[cfg(target_os = "linux")]
+    pub fn present_pixels(&mut self, pixels: &[u32], size: PhySize, damage: &[PhyRect]) {
+        self.window.present_pixels(pixels, size, damage);
+    }
+
     /// If provided, then an OpenGL context will be created for this window. You'll be able to
     /// access this context through [crate::Window::gl_context].
     #[cfg(feature = "opengl")]
     pub fn gl_context(&self) -> Option<&crate::gl::GlContext> {
         self.window.gl_context()
     }
-    /// If provided, then an Vulkan context will be created for this window. You'll be able to
-    /// access this context through [crate::Window::vk_context].
+    /// The Vulkan context created for [WindowOpenOptions::vk_config]. Returns `None` if no
+    /// context was requested, or if creating it failed. See [Self::vk_context_error] for the
+    /// latter.
     #[cfg(feature = "vulkan")]
     pub fn vk_context(&self) -> Option<&crate::vk::VkContext> {
         self.window.vk_context()
     }
+
+    /// Mutable access to the Vulkan context, needed to acquire and present swapchain images.
+    #[cfg(feature = "vulkan")]
+    pub fn vk_context_mut(&mut self) -> Option<&mut crate::vk::VkContext> {
+        self.window.vk_context_mut()
+    }
+
+    /// The reason the Vulkan context requested through [WindowOpenOptions::vk_config] could not be
+    /// created, if it failed.
+    #[cfg(feature = "vulkan")]
+    pub fn vk_context_error(&self) -> Option<&crate::vk::VkError> {
+        self.window.vk_context_error()
+    }
 }
 
 unsafe impl<'a> HasRawWindowHandle for Window<'a> {
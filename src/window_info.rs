@@ -120,6 +120,22 @@ impl Size {
     }
 }
 
+/// A rectangle in actual physical coordinates
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct PhyRect {
+    pub x: i32,
+    pub y: i32,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl PhyRect {
+    /// Create a new rectangle in actual physical coordinates
+    pub fn new(x: i32, y: i32, width: u32, height: u32) -> Self {
+        Self { x, y, width, height }
+    }
+}
+
 /// An actual size in physical coordinates
 #[derive(Debug, Copy, Clone, PartialEq)]
 pub struct PhySize {
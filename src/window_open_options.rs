@@ -3,10 +3,116 @@ use crate::Size;
 /// The dpi scaling policy of the window
 #[derive(Debug, Clone, Copy, PartialEq)]
 pub enum WindowScalePolicy {
-    /// Use the system's dpi scale factor
+    /// Use the system's dpi scale factor. On X11 this is `Xft.dpi` if it's set, and otherwise the
+    /// pixel density of the monitor the window is on.
     SystemScaleFactor,
     /// Use the given dpi scale factor (e.g. `1.0` = 96 dpi)
     ScaleFactor(f64),
+    /// Use the system's dpi scale factor, resolved from the first of the configured sources that
+    /// has a value and then rounded and clamped. The source that was used can be queried with
+    /// [crate::Window::scale_source]. See [AutoScale::default] for the default configuration.
+    Auto(AutoScale),
+}
+
+impl WindowScalePolicy {
+    /// Apply this policy to a scale factor reported by the system. Returns `None` if the window
+    /// uses a fixed scale factor.
+    pub(crate) fn apply_to_system_scale(&self, scale: f64) -> Option<f64> {
+        match self {
+            WindowScalePolicy::SystemScaleFactor => Some(scale),
+            WindowScalePolicy::ScaleFactor(_) => None,
+            WindowScalePolicy::Auto(auto_scale) => Some(auto_scale.apply(scale)),
+        }
+    }
+}
+
+/// How the scale factor is determined for [WindowScalePolicy::Auto].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AutoScale {
+    /// The sources to try in order. The first source that has a value is used, and if none of them
+    /// do then the scale factor will be `1.0` before rounding and clamping. Only used on X11, other
+    /// platforms always use the scale factor reported by the operating system.
+    pub sources: &'static [ScaleSource],
+    /// How to round the resolved scale factor.
+    pub rounding: ScaleRounding,
+    /// The smallest scale factor that will be used.
+    pub min_scale: f64,
+    /// The largest scale factor that will be used.
+    pub max_scale: f64,
+}
+
+impl AutoScale {
+    /// Round and clamp a scale factor according to this configuration.
+    pub fn apply(&self, scale: f64) -> f64 {
+        let scale = match self.rounding {
+            ScaleRounding::None => scale,
+            ScaleRounding::NearestQuarter => (scale * 4.0).round() / 4.0,
+        };
+
+        scale.clamp(self.min_scale, self.max_scale)
+    }
+}
+
+impl Default for AutoScale {
+    /// Try the environment variables set by the user first, followed by the same sources as
+    /// [WindowScalePolicy::SystemScaleFactor]: `Xft.dpi`, the pixel density of the monitor the
+    /// window is on, and then that of the entire screen. The result is rounded to the nearest
+    /// quarter and clamped to `0.5..=4.0`, which gets rid of the odd factors caused by inaccurate
+    /// physical monitor sizes.
+    fn default() -> Self {
+        Self {
+            sources: &[
+                ScaleSource::GdkScale,
+                ScaleSource::QtScaleFactor,
+                ScaleSource::XftDpi,
+                ScaleSource::MonitorDpi,
+                ScaleSource::ScreenDpi,
+            ],
+            rounding: ScaleRounding::NearestQuarter,
+            min_scale: 0.5,
+            max_scale: 4.0,
+        }
+    }
+}
+
+/// A place the system scale factor can be read from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ScaleSource {
+    /// The `GDK_SCALE` environment variable used by GTK applications
+    GdkScale,
+    /// The `QT_SCALE_FACTOR` environment variable used by Qt applications
+    QtScaleFactor,
+    /// The `Xft.dpi` X resource, which most desktop environments set based on the user's settings
+    XftDpi,
+    /// The pixel density of the monitor the window is on, computed from its physical size
+    MonitorDpi,
+    /// The pixel density of the entire X11 screen, computed from its physical size
+    ScreenDpi,
+    /// The scale factor reported by the operating system on platforms other than X11
+    System,
+}
+
+/// How [WindowScalePolicy::Auto] rounds the scale factor.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ScaleRounding {
+    /// Use the scale factor as is
+    None,
+    /// Round to the nearest multiple of `0.25`
+    NearestQuarter,
+}
+
+/// How touch screen input is reported to the window handler
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum TouchPolicy {
+    /// Only report touches as emulated mouse events. No [crate::Event::Touch] events are sent.
+    /// This is the default, matching platforms without touch support.
+    #[default]
+    MouseOnly,
+    /// Report touches as [crate::Event::Touch] events, and also report the first touch as emulated
+    /// mouse events
+    TouchAndMouse,
+    /// Only report touches as [crate::Event::Touch] events
+    TouchOnly,
 }
 
 /// The options for opening a new window
@@ -22,10 +128,65 @@ pub struct WindowOpenOptions {
     /// The dpi scaling policy
     pub scale: WindowScalePolicy,
 
+    /// Create the window with an alpha channel so it can be drawn with per-pixel transparency.
+    /// Transparency only takes effect while a compositing manager is running, see
+    /// [crate::WindowEvent::CompositorChanged]. If the X server has no visual with an alpha channel
+    /// the window is opaque instead, and `CompositorChanged(false)` is sent. Currently only
+    /// supported on X11.
+    pub transparent: bool,
+
+    /// Whether touches are reported as [crate::Event::Touch] events, emulated mouse events, or
+    /// both. Touch events are currently only supported on X11, touches are always reported as
+    /// mouse events on other platforms.
+    pub touch: TouchPolicy,
+
     /// If provided, then an OpenGL context will be created for this window. You'll be able to
     /// access this context through [crate::Window::gl_context].
     #[cfg(feature = "opengl")]
     pub gl_config: Option<crate::gl::GlConfig>,
+    /// If provided, then a Vulkan context will be created for this window. You'll be able to
+    /// access this context through [crate::Window::vk_context]. No context is created if this is
+    /// `None`. If creating the context fails the window is still opened, and the error can be
+    /// retrieved with [crate::Window::vk_context_error].
     #[cfg(feature = "vulkan")]
     pub vk_config: Option<crate::vk::VkConfig>,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn auto_scale_rounds_to_nearest_quarter() {
+        let auto_scale = AutoScale::default();
+
+        assert_eq!(auto_scale.apply(1.0), 1.0);
+        assert_eq!(auto_scale.apply(1.1), 1.0);
+        assert_eq!(auto_scale.apply(1.2), 1.25);
+        assert_eq!(auto_scale.apply(1.66), 1.75);
+    }
+
+    #[test]
+    fn auto_scale_without_rounding() {
+        let auto_scale = AutoScale { rounding: ScaleRounding::None, ..AutoScale::default() };
+
+        assert_eq!(auto_scale.apply(1.1), 1.1);
+    }
+
+    #[test]
+    fn auto_scale_clamps() {
+        let auto_scale = AutoScale::default();
+
+        assert_eq!(auto_scale.apply(0.1), 0.5);
+        assert_eq!(auto_scale.apply(8.0), 4.0);
+
+        let auto_scale = AutoScale {
+            rounding: ScaleRounding::None,
+            min_scale: 1.0,
+            max_scale: 2.0,
+            ..auto_scale
+        };
+        assert_eq!(auto_scale.apply(0.75), 1.0);
+        assert_eq!(auto_scale.apply(2.1), 2.0);
+    }
+}
@@ -6,3 +6,7 @@ pub use window::*;
 
 mod cursor;
 mod keyboard;
+mod software_buffer;
+mod timers;
+mod xembed;
+mod xinput;
@@ -0,0 +1,423 @@
+use std::os::raw::c_void;
+use std::ptr;
+
+use xcb::shm;
+
+use super::XcbConnection;
+use crate::{PhyRect, PhySize};
+
+/// The size of a `PutImage` request's header in bytes.
+const PUT_IMAGE_HEADER_SIZE: usize = 24;
+
+/// Draws CPU rendered pixels to a window for [crate::Window::present_pixels]. The pixels are
+/// transferred through a MIT-SHM segment when the X server supports it, and through regular
+/// `PutImage` requests otherwise (for instance when the X server runs on another machine, or when
+/// the window's visual doesn't store pixels in the same format as the buffer).
+pub(super) struct SoftwareBuffer {
+    gc: u32,
+    depth: u8,
+    format: PixelFormat,
+    /// Transparent windows use the pixels' alpha channel, other windows ignore it.
+    transparent: bool,
+    /// Set to false when the X server does not support MIT-SHM, or when attaching a segment
+    /// failed.
+    use_shm: bool,
+    /// The event type of MIT-SHM's `Completion` event.
+    shm_completion_event: Option<u8>,
+    /// Allocated on the first present and grown as needed.
+    shm_segment: Option<ShmSegment>,
+}
+
+/// How the window's visual stores a pixel.
+struct PixelFormat {
+    bytes_per_pixel: usize,
+    /// Each row of pixels is padded to a multiple of this many bytes.
+    scanline_pad: usize,
+    red_mask: u32,
+    green_mask: u32,
+    blue_mask: u32,
+}
+
+/// A shared memory segment attached to both this process and the X server.
+struct ShmSegment {
+    seg: shm::Seg,
+    addr: *mut u32,
+    /// The size of the segment in pixels.
+    len: usize,
+    /// Set while the X server may still be reading from the segment. Cleared when the `Completion`
+    /// event for the last `ShmPutImage` request arrives.
+    busy: bool,
+}
+
+impl SoftwareBuffer {
+    /// Returns `None` if the window no longer exists, or if its visual is not a TrueColor visual
+    /// with 16, 24, or 32 bits per pixel.
+    pub fn new(xcb_connection: &XcbConnection, window_id: u32, transparent: bool) -> Option<Self> {
+        let conn = &xcb_connection.conn;
+
+        let geometry = xcb::get_geometry(conn, window_id);
+        let attributes = xcb::get_window_attributes(conn, window_id);
+        let depth = geometry.get_reply().ok()?.depth();
+        let visual_id = attributes.get_reply().ok()?.visual();
+
+        let format = PixelFormat::new(conn, depth, visual_id)?;
+
+        let gc = conn.generate_id();
+        xcb::create_gc(conn, gc, window_id, &[(xcb::GC_GRAPHICS_EXPOSURES, 0)]);
+
+        // Only buffers that can be copied to the window as is can be drawn through MIT-SHM
+        let shm_completion_event = conn
+            .get_extension_data(shm::id())
+            .filter(|data| data.present() && format.is_xrgb())
+            .filter(|_| shm::query_version(conn).get_reply().is_ok())
+            .map(|data| data.first_event() + shm::COMPLETION);
+
+        Some(Self {
+            gc,
+            depth,
+            format,
+            transparent,
+            use_shm: shm_completion_event.is_some(),
+            shm_completion_event,
+            shm_segment: None,
+        })
+    }
+
+    /// Handle MIT-SHM's `Completion` event. Returns `false` if `event_type` is not that event.
+    pub fn handle_event(&mut self, event_type: u8) -> bool {
+        if Some(event_type) != self.shm_completion_event {
+            return false;
+        }
+
+        if let Some(segment) = &mut self.shm_segment {
+            segment.busy = false;
+        }
+
+        true
+    }
+
+    pub fn present(
+        &mut self, xcb_connection: &XcbConnection, window_id: u32, pixels: &[u32], size: PhySize,
+        damage: &[PhyRect],
+    ) {
+        assert!(
+            pixels.len() >= size.width as usize * size.height as usize,
+            "The pixel buffer is smaller than the specified size"
+        );
+
+        let full_damage = [PhyRect::new(0, 0, size.width, size.height)];
+        let damage = if damage.is_empty() { &full_damage[..] } else { damage };
+        let rects: Vec<_> = damage.iter().filter_map(|rect| clip_rect(rect, size)).collect();
+        if rects.is_empty() {
+            return;
+        }
+
+        if self.use_shm {
+            if !self.ensure_shm_segment(xcb_connection, pixels.len()) {
+                self.use_shm = false;
+            } else if !self.shm_segment.as_ref().unwrap().busy {
+                self.present_shm(xcb_connection, window_id, pixels, size, &rects);
+                return;
+            }
+
+            // If the X server hasn't finished reading the previous frame yet, we'll send this one
+            // with `PutImage` instead of waiting for it
+        }
+
+        self.present_put_image(xcb_connection, window_id, pixels, size, &rects);
+    }
+
+    /// Make sure there's a shared memory segment that can hold at least `len` pixels. Returns
+    /// false if a segment could not be created.
+    fn ensure_shm_segment(&mut self, xcb_connection: &XcbConnection, len: usize) -> bool {
+        // A segment the X server may still be reading from is detached after those reads since the
+        // X server processes requests in order
+        match &self.shm_segment {
+            Some(segment) if segment.len >= len => return true,
+            Some(segment) => {
+                shm::detach(&xcb_connection.conn, segment.seg);
+                self.shm_segment = None;
+            }
+            None => (),
+        }
+
+        self.shm_segment = unsafe { ShmSegment::new(xcb_connection, len) };
+        self.shm_segment.is_some()
+    }
+
+    fn present_shm(
+        &mut self, xcb_connection: &XcbConnection, window_id: u32, pixels: &[u32], size: PhySize,
+        rects: &[ClippedRect],
+    ) {
+        let conn = &xcb_connection.conn;
+        let transparent = self.transparent;
+        let segment = self.shm_segment.as_mut().unwrap();
+        let shared_pixels = unsafe { std::slice::from_raw_parts_mut(segment.addr, segment.len) };
+
+        for (i, rect) in rects.iter().enumerate() {
+            for row in rect.y..rect.y + rect.height {
+                let start = row as usize * size.width as usize + rect.x as usize;
+                let end = start + rect.width as usize;
+                for (target, pixel) in shared_pixels[start..end].iter_mut().zip(&pixels[start..end])
+                {
+                    *target = if transparent { *pixel } else { *pixel | 0xff000000 };
+                }
+            }
+
+            // The X server reads from the segment asynchronously, so we'll ask for a `Completion`
+            // event after the last rectangle before the segment is written to again
+            let send_event = i == rects.len() - 1;
+            shm::put_image(
+                conn,
+                window_id,
+                self.gc,
+                size.width as u16,
+                size.height as u16,
+                rect.x as u16,
+                rect.y as u16,
+                rect.width as u16,
+                rect.height as u16,
+                rect.x as i16,
+                rect.y as i16,
+                self.depth,
+                xcb::IMAGE_FORMAT_Z_PIXMAP as u8,
+                send_event as u8,
+                segment.seg,
+                0,
+            );
+        }
+
+        segment.busy = true;
+        conn.flush();
+    }
+
+    fn present_put_image(
+        &self, xcb_connection: &XcbConnection, window_id: u32, pixels: &[u32], size: PhySize,
+        rects: &[ClippedRect],
+    ) {
+        let conn = &xcb_connection.conn;
+        let lsb_first = conn.get_setup().image_byte_order() == xcb::IMAGE_ORDER_LSB_FIRST as u8;
+        let max_request_size = conn.get_maximum_request_length() as usize * 4;
+
+        let mut data = Vec::new();
+        for rect in rects {
+            // Larger images need to be split up to fit within the maximum request size
+            let row_size = self.format.row_size(rect.width);
+            let rows_per_request =
+                (max_request_size.saturating_sub(PUT_IMAGE_HEADER_SIZE) / row_size).max(1) as u32;
+
+            let mut y = rect.y;
+            while y < rect.y + rect.height {
+                let num_rows = rows_per_request.min(rect.y + rect.height - y);
+
+                data.clear();
+                for row in y..y + num_rows {
+                    let start = row as usize * size.width as usize + rect.x as usize;
+                    for pixel in &pixels[start..start + rect.width as usize] {
+                        self.encode_pixel(*pixel, lsb_first, &mut data);
+                    }
+
+                    data.resize(data.len().next_multiple_of(self.format.scanline_pad), 0);
+                }
+
+                xcb::put_image(
+                    conn,
+                    xcb::IMAGE_FORMAT_Z_PIXMAP as u8,
+                    window_id,
+                    self.gc,
+                    rect.width as u16,
+                    num_rows as u16,
+                    rect.x as i16,
+                    y as i16,
+                    0,
+                    self.depth,
+                    &data,
+                );
+
+                y += num_rows;
+            }
+        }
+
+        conn.flush();
+    }
+
+    /// Append `pixel` to `data` in the window's pixel format. The upper 8 bits of the pixels are
+    /// ignored for opaque windows, but the window may still have an alpha channel.
+    fn encode_pixel(&self, pixel: u32, lsb_first: bool, data: &mut Vec<u8>) {
+        let pixel = if !self.format.is_xrgb() {
+            self.format.convert(pixel)
+        } else if self.transparent {
+            pixel
+        } else {
+            pixel | 0xff000000
+        };
+
+        let bytes = if lsb_first { pixel.to_le_bytes() } else { pixel.to_be_bytes() };
+        let bytes = if lsb_first {
+            &bytes[..self.format.bytes_per_pixel]
+        } else {
+            &bytes[4 - self.format.bytes_per_pixel..]
+        };
+        data.extend_from_slice(bytes);
+    }
+}
+
+impl PixelFormat {
+    /// Look up the pixel format used by windows with the given depth and visual. Returns `None` for
+    /// formats we can't convert to.
+    fn new(conn: &xcb::Connection, depth: u8, visual_id: u32) -> Option<Self> {
+        let setup = conn.get_setup();
+        let pixmap_format = setup.pixmap_formats().find(|format| format.depth() == depth)?;
+        let visual = setup
+            .roots()
+            .flat_map(|screen| screen.allowed_depths())
+            .flat_map(|depth| depth.visuals())
+            .find(|visual| visual.visual_id() == visual_id)?;
+
+        let class = visual.class();
+        if class != xcb::VISUAL_CLASS_TRUE_COLOR as u8
+            && class != xcb::VISUAL_CLASS_DIRECT_COLOR as u8
+        {
+            return None;
+        }
+
+        let bytes_per_pixel = match pixmap_format.bits_per_pixel() {
+            bits @ (16 | 24 | 32) => bits as usize / 8,
+            _ => return None,
+        };
+
+        Some(Self {
+            bytes_per_pixel,
+            scanline_pad: (pixmap_format.scanline_pad() as usize / 8).max(1),
+            red_mask: visual.red_mask(),
+            green_mask: visual.green_mask(),
+            blue_mask: visual.blue_mask(),
+        })
+    }
+
+    /// Whether pixels in `0x00RRGGBB` format can be used as is. This is the case for the usual
+    /// 24-bit visuals and the 32-bit ARGB visual.
+    fn is_xrgb(&self) -> bool {
+        self.bytes_per_pixel == 4
+            && (self.red_mask, self.green_mask, self.blue_mask) == (0xff0000, 0xff00, 0xff)
+    }
+
+    /// The size of a row of `width` pixels in bytes, including padding.
+    fn row_size(&self, width: u32) -> usize {
+        (width as usize * self.bytes_per_pixel).next_multiple_of(self.scanline_pad)
+    }
+
+    /// Convert a pixel in `0x00RRGGBB` format to this format.
+    fn convert(&self, pixel: u32) -> u32 {
+        convert_channel(pixel >> 16, self.red_mask)
+            | convert_channel(pixel >> 8, self.green_mask)
+            | convert_channel(pixel, self.blue_mask)
+    }
+}
+
+/// Scale the 8-bit color channel in the lower bits of `value` to the number of bits in `mask`, and
+/// move it to `mask`'s position.
+fn convert_channel(value: u32, mask: u32) -> u32 {
+    let value = value & 0xff;
+    let bits = mask.count_ones();
+    let value = if bits >= 8 { value << (bits - 8) } else { value >> (8 - bits) };
+
+    (value << mask.trailing_zeros()) & mask
+}
+
+impl ShmSegment {
+    /// Create a shared memory segment for `len` pixels and attach it to the X server. Returns
+    /// `None` if either step fails.
+    unsafe fn new(xcb_connection: &XcbConnection, len: usize) -> Option<Self> {
+        let conn = &xcb_connection.conn;
+
+        let id = libc::shmget(libc::IPC_PRIVATE, len * 4, libc::IPC_CREAT | 0o600);
+        if id == -1 {
+            return None;
+        }
+
+        let addr = libc::shmat(id, ptr::null(), 0);
+        if addr == usize::MAX as *mut c_void {
+            libc::shmctl(id, libc::IPC_RMID, ptr::null_mut());
+            return None;
+        }
+
+        let seg = conn.generate_id();
+        let attached = shm::attach_checked(conn, seg, id as u32, true).request_check().is_ok();
+
+        // The segment is only actually removed once both we and the X server have detached from
+        // it, so this makes sure it doesn't outlive the process
+        libc::shmctl(id, libc::IPC_RMID, ptr::null_mut());
+
+        if attached {
+            Some(ShmSegment { seg, addr: addr as *mut u32, len, busy: false })
+        } else {
+            libc::shmdt(addr);
+            None
+        }
+    }
+}
+
+impl Drop for ShmSegment {
+    fn drop(&mut self) {
+        // The X server detaches the segment on its end when the connection is closed
+        unsafe { libc::shmdt(self.addr as *const c_void) };
+    }
+}
+
+/// A damage rectangle clipped to the pixel buffer's bounds.
+struct ClippedRect {
+    x: u32,
+    y: u32,
+    width: u32,
+    height: u32,
+}
+
+fn clip_rect(rect: &PhyRect, size: PhySize) -> Option<ClippedRect> {
+    let x = rect.x.max(0) as u32;
+    let y = rect.y.max(0) as u32;
+    let right = (i64::from(rect.x) + i64::from(rect.width)).clamp(0, i64::from(size.width)) as u32;
+    let bottom =
+        (i64::from(rect.y) + i64::from(rect.height)).clamp(0, i64::from(size.height)) as u32;
+
+    if x < right && y < bottom {
+        Some(ClippedRect { x, y, width: right - x, height: bottom - y })
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn clip_rect_inside_buffer() {
+        let rect = clip_rect(&PhyRect::new(10, 20, 30, 40), PhySize::new(100, 100)).unwrap();
+
+        assert_eq!((rect.x, rect.y, rect.width, rect.height), (10, 20, 30, 40));
+    }
+
+    #[test]
+    fn clip_rect_partially_outside_buffer() {
+        let rect = clip_rect(&PhyRect::new(-10, 90, 30, 40), PhySize::new(100, 100)).unwrap();
+
+        assert_eq!((rect.x, rect.y, rect.width, rect.height), (0, 90, 20, 10));
+    }
+
+    #[test]
+    fn clip_rect_outside_buffer() {
+        let size = PhySize::new(100, 100);
+
+        assert!(clip_rect(&PhyRect::new(100, 0, 10, 10), size).is_none());
+        assert!(clip_rect(&PhyRect::new(-20, 0, 10, 10), size).is_none());
+        assert!(clip_rect(&PhyRect::new(0, 0, 0, 10), size).is_none());
+    }
+
+    #[test]
+    fn clip_rect_does_not_overflow() {
+        let rect = clip_rect(&PhyRect::new(i32::MAX, 0, u32::MAX, 10), PhySize::new(100, 100));
+
+        assert!(rect.is_none());
+    }
+}
@@ -0,0 +1,132 @@
+use std::time::{Duration, Instant};
+
+/// The timers set with [crate::Window::set_timer]. There are rarely more than a handful of
+/// timers, so they're simply stored in a list.
+pub(super) struct Timers {
+    timers: Vec<Timer>,
+}
+
+struct Timer {
+    id: u32,
+    interval: Duration,
+    repeat: bool,
+    deadline: Instant,
+}
+
+impl Timers {
+    pub fn new() -> Self {
+        Self { timers: Vec::new() }
+    }
+
+    /// Start a timer, replacing the existing timer with the same ID if there is one.
+    pub fn set(&mut self, id: u32, interval: Duration, repeat: bool) {
+        // A repeating timer with a zero interval would fire forever without ever yielding back to
+        // the event loop
+        let interval = interval.max(Duration::from_millis(1));
+
+        self.cancel(id);
+        self.timers.push(Timer { id, interval, repeat, deadline: Instant::now() + interval });
+    }
+
+    pub fn cancel(&mut self, id: u32) {
+        self.timers.retain(|timer| timer.id != id);
+    }
+
+    /// The time the next timer should fire at, if there are any timers.
+    pub fn next_deadline(&self) -> Option<Instant> {
+        self.timers.iter().map(|timer| timer.deadline).min()
+    }
+
+    /// Returns the ID of a timer that should have fired by `now`, and reschedules or removes that
+    /// timer. This returns a single timer at a time so timers that are cancelled while handling an
+    /// earlier timer don't fire anymore.
+    pub fn pop_expired(&mut self, now: Instant) -> Option<u32> {
+        let index = self.timers.iter().position(|timer| timer.deadline <= now)?;
+
+        let timer = &mut self.timers[index];
+        let id = timer.id;
+        if timer.repeat {
+            // If the event loop was blocked for a while this skips the missed intervals instead of
+            // firing the timer multiple times in a row
+            timer.deadline += timer.interval;
+            if timer.deadline <= now {
+                timer.deadline = now + timer.interval;
+            }
+        } else {
+            self.timers.remove(index);
+        }
+
+        Some(id)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pop_expired_returns_nothing_before_deadline() {
+        let mut timers = Timers::new();
+        timers.set(1, Duration::from_secs(60), false);
+
+        assert_eq!(timers.pop_expired(Instant::now()), None);
+    }
+
+    #[test]
+    fn pop_expired_removes_one_shot_timers() {
+        let mut timers = Timers::new();
+        timers.set(1, Duration::from_millis(10), false);
+
+        let now = Instant::now() + Duration::from_millis(20);
+        assert_eq!(timers.pop_expired(now), Some(1));
+        assert_eq!(timers.pop_expired(now), None);
+        assert_eq!(timers.next_deadline(), None);
+    }
+
+    #[test]
+    fn pop_expired_reschedules_repeating_timers() {
+        let mut timers = Timers::new();
+        timers.set(1, Duration::from_millis(10), true);
+        let deadline = timers.next_deadline().unwrap();
+
+        assert_eq!(timers.pop_expired(deadline), Some(1));
+        assert_eq!(timers.pop_expired(deadline), None);
+        assert_eq!(timers.next_deadline(), Some(deadline + Duration::from_millis(10)));
+    }
+
+    #[test]
+    fn pop_expired_skips_missed_intervals() {
+        let mut timers = Timers::new();
+        timers.set(1, Duration::from_millis(10), true);
+
+        let now = timers.next_deadline().unwrap() + Duration::from_millis(55);
+        assert_eq!(timers.pop_expired(now), Some(1));
+        assert_eq!(timers.pop_expired(now), None);
+        assert_eq!(timers.next_deadline(), Some(now + Duration::from_millis(10)));
+    }
+
+    #[test]
+    fn pop_expired_returns_one_timer_at_a_time() {
+        let mut timers = Timers::new();
+        timers.set(1, Duration::from_millis(10), false);
+        timers.set(2, Duration::from_millis(10), false);
+
+        let now = Instant::now() + Duration::from_millis(20);
+        let first = timers.pop_expired(now).unwrap();
+        let second = timers.pop_expired(now).unwrap();
+        assert_ne!(first, second);
+        assert_eq!(timers.pop_expired(now), None);
+    }
+
+    #[test]
+    fn cancelled_timers_do_not_fire() {
+        let mut timers = Timers::new();
+        timers.set(1, Duration::from_millis(10), false);
+        timers.set(2, Duration::from_millis(10), false);
+
+        let now = Instant::now() + Duration::from_millis(20);
+        let fired = timers.pop_expired(now).unwrap();
+        timers.cancel(if fired == 1 { 2 } else { 1 });
+        assert_eq!(timers.pop_expired(now), None);
+    }
+}
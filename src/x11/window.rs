@@ -1,4 +1,6 @@
+use std::collections::HashMap;
 use std::ffi::c_void;
+use std::os::unix::io::{AsRawFd, RawFd};
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::mpsc;
 use std::sync::Arc;
@@ -12,18 +14,27 @@ use raw_window_handle::{
 use xcb::ffi::xcb_screen_t;
 use xcb::StructPtr;
 
+use super::xcb_connection::SYSTEM_SCALE_SOURCES;
 use super::XcbConnection;
 use crate::{
-    Event, MouseButton, MouseCursor, MouseEvent, PhyPoint, PhySize, ScrollDelta, Size, WindowEvent,
-    WindowHandler, WindowInfo, WindowOpenOptions, WindowScalePolicy,
+    CursorGrabMode, Event, MonitorInfo, MouseButton, MouseCursor, MouseEvent, PenEvent, PhyPoint,
+    PhyRect, PhySize, Point, PumpStatus, ScaleSource, ScrollDelta, Size, TouchEvent, TouchPhase,
+    TouchPolicy, WindowEvent, WindowHandler, WindowInfo, WindowOpenOptions, WindowScalePolicy,
 };
 
 use super::keyboard::{convert_key_press_event, convert_key_release_event, key_mods};
+use super::software_buffer::SoftwareBuffer;
+use super::timers::Timers;
+use super::xembed::{
+    self, XEmbedState, XEMBED_EMBEDDED_NOTIFY, XEMBED_FOCUS_IN, XEMBED_FOCUS_OUT,
+    XEMBED_REQUEST_FOCUS, XEMBED_WINDOW_ACTIVATE, XEMBED_WINDOW_DEACTIVATE,
+};
+use super::xinput::{self, DeviceEvent, DeviceValuators};
 
 #[cfg(feature = "opengl")]
 use crate::gl::{platform, GlContext};
 #[cfg(feature = "vulkan")]
-use crate::vk::{platform, VkContext};
+use crate::vk::{platform as vk_platform, VkContext, VkError};
 
 pub struct WindowHandle {
     raw_window_handle: Option<RawWindowHandle>,
@@ -93,20 +104,83 @@ struct WindowInner {
     xcb_connection: XcbConnection,
     window_id: u32,
     window_info: WindowInfo,
+    scale_policy: WindowScalePolicy,
+    /// Where the current scale factor came from, see [crate::Window::scale_source].
+    scale_source: Option<ScaleSource>,
     visual_id: u32,
+    /// Whether the window was opened with [WindowOpenOptions::transparent].
+    transparent: bool,
     mouse_cursor: MouseCursor,
+    cursor_grab: CursorGrabMode,
+    /// Where the cursor was when it was locked with [CursorGrabMode::Locked]. It's moved back
+    /// there when it gets unlocked.
+    locked_cursor_position: Option<PhyPoint>,
+    /// Whether the pointer is grabbed through [crate::Window::capture_mouse].
+    mouse_captured: bool,
+    /// Used for smooth scrolling, touch pressure, and pen input. Empty if the X server doesn't
+    /// support XInput 2.1.
+    valuators: DeviceValuators,
+    /// The time of the last XInput event we reported scrolling for. The X server emulates core
+    /// scroll wheel button events with the same time, which should then be ignored. XInput events
+    /// are not delivered during a core pointer grab, so the button events are used there.
+    last_xi_scroll_time: Option<u32>,
+    touch_policy: TouchPolicy,
+    /// The touches that have started but not yet ended, by their touch ID.
+    active_touches: HashMap<u32, ActiveTouch>,
 
     frame_interval: Duration,
+    last_frame: Instant,
+    timers: Timers,
     event_loop_running: bool,
     close_requested: bool,
 
     new_physical_size: Option<PhySize>,
+    /// Set when a `ConfigureNotify` event was received, since the window may have moved.
+    position_changed: bool,
+    /// The outer position sent with the last [WindowEvent::Moved] event.
+    outer_position: Option<PhyPoint>,
+
+    /// Whether the window is currently viewable, see [WindowEvent::Shown].
+    is_visible: bool,
+    is_occluded: bool,
+    is_maximized: bool,
+    is_minimized: bool,
+    has_focus: bool,
+    xembed: XEmbedState,
+    /// Whether the window was opened with a parent window, as opposed to being a top level window.
+    is_parented: bool,
     parent_handle: Option<ParentHandle>,
 
+    /// The `_NET_WM_CM_Sn` selection, which is owned by the compositing manager if one is running.
+    compositor_selection: Option<u32>,
+    /// Used to receive the XFixes events for changes to `compositor_selection`'s owner.
+    xfixes_event_base: Option<u8>,
+    is_composited: bool,
+
+    /// Used to receive the RandR events for changes to the monitor configuration.
+    randr_event_base: Option<u8>,
+    /// Set when the system scale factor may have changed because the monitor configuration or
+    /// `Xft.dpi` changed.
+    scale_changed: bool,
+    /// The RandR monitor the window's center was on when the scale factor was last resolved. Only
+    /// tracked when the scale factor depends on the monitor.
+    scale_monitor: Option<usize>,
+
+    /// Created on the first call to [Window::present_pixels].
+    software_buffer: Option<SoftwareBuffer>,
+
     #[cfg(feature = "opengl")]
     gl_context: Option<GlContext>,
+    /// `None` if no Vulkan context was requested.
     #[cfg(feature = "vulkan")]
-    vk_context: Option<VkContext>,
+    vk_context: Option<Result<VkContext, VkError>>,
+}
+
+/// The last known state of a touch, since touch update events only contain the values that
+/// changed.
+struct ActiveTouch {
+    position: Point,
+    force: Option<f64>,
 }
 
 pub struct Window<'a> {
@@ -168,6 +242,38 @@ impl<'a> Window<'a> {
         });
     }
 
+    pub fn open_parented_embedded<P, H, B>(
+        parent: &P, options: WindowOpenOptions, build: B,
+    ) -> EmbeddedWindow
+    where
+        P: HasRawWindowHandle,
+        H: WindowHandler + 'static,
+        B: FnOnce(&mut crate::Window) -> H,
+    {
+        // Convert parent into something that X understands
+        let parent_id = match parent.raw_window_handle() {
+            RawWindowHandle::Xlib(h) => h.window as u32,
+            RawWindowHandle::Xcb(h) => h.window,
+            h => panic!("unsupported parent handle type {:?}", h),
+        };
+
+        let (mut inner, handler) = Self::create(Some(parent_id), options, build, None);
+        inner.event_loop_running = true;
+
+        EmbeddedWindow { inner, handler: Some(Box::new(handler)) }
+    }
+
+    pub fn open_with_event_loop<H, B>(options: WindowOpenOptions, build: B) -> EventLoop
+    where
+        H: WindowHandler + 'static,
+        B: FnOnce(&mut crate::Window) -> H,
+    {
+        let (mut inner, handler) = Self::create(None, options, build, None);
+        inner.event_loop_running = true;
+
+        EventLoop { inner, handler: Some(Box::new(handler)) }
+    }
+
     fn window_thread<H, B>(
         parent: Option<u32>, options: WindowOpenOptions, build: B,
         tx: mpsc::SyncSender<WindowOpenResult>, parent_handle: Option<ParentHandle>,
@@ -175,6 +281,29 @@ impl<'a> Window<'a> {
         H: WindowHandler + 'static,
         B: FnOnce(&mut crate::Window) -> H,
         B: Send + 'static,
+    {
+        let (mut inner, mut handler) = Self::create(parent, options, build, parent_handle);
+
+        let window = crate::Window::new(Window { inner: &mut inner });
+        let _ = tx.send(Ok(SendableRwh(window.raw_window_handle())));
+
+        inner.run_event_loop(&mut handler);
+
+        // The handler may still own objects created from the Vulkan device, and the Vulkan surface
+        // needs to be destroyed while the X11 connection is still open
+        drop(handler);
+        #[cfg(feature = "vulkan")]
+        drop(inner.vk_context.take());
+    }
+
+    /// Create the window and its window handler on the current thread.
+    fn create<H, B>(
+        parent: Option<u32>, options: WindowOpenOptions, build: B,
+        parent_handle: Option<ParentHandle>,
+    ) -> (WindowInner, H)
+    where
+        H: WindowHandler + 'static,
+        B: FnOnce(&mut crate::Window) -> H,
     {
         // Connect to the X server
         // FIXME: baseview error type instead of unwrap()
@@ -195,9 +324,17 @@ impl<'a> Window<'a> {
             &[(xcb::GC_FOREGROUND, screen.black_pixel()), (xcb::GC_GRAPHICS_EXPOSURES, 0)],
         );
 
-        let scaling = match options.scale {
-            WindowScalePolicy::SystemScaleFactor => xcb_connection.get_scaling().unwrap_or(1.0),
-            WindowScalePolicy::ScaleFactor(scale) => scale,
+        // The window doesn't exist yet, so we'll use the monitor containing the parent window's
+        // origin. This will be corrected once the window has been positioned.
+        let (scaling, scale_source) = match options.scale {
+            WindowScalePolicy::ScaleFactor(scale) => (scale, None),
+            policy => {
+                let position = xcb_connection
+                    .translate_to_root(parent_id, PhyPoint::new(0, 0))
+                    .unwrap_or(PhyPoint::new(0, 0));
+
+                resolve_scaling(&xcb_connection, policy, position)
+            }
         };
 
         let window_info = WindowInfo::from_logical_size(options.size, scaling);
@@ -208,18 +345,25 @@ impl<'a> Window<'a> {
         // configuration, find the visual that matches that framebuffer config, create the window
         // with that visual, and then finally create an OpenGL context for the window. If we don't
         // use OpenGL, then we'll just take a random visual with a 32-bit depth.
-        let create_default_config = || {
-            Self::find_visual_for_depth(&screen, 32)
-                .map(|visual| (32, visual))
-                .unwrap_or((xcb::COPY_FROM_PARENT as u8, xcb::COPY_FROM_PARENT as u32))
+        let create_default_config = || match Self::find_visual_for_depth(&screen, 32) {
+            Some(visual) => (32, visual),
+            None => (xcb::COPY_FROM_PARENT as u8, xcb::COPY_FROM_PARENT as u32),
         };
         #[cfg(feature = "opengl")]
         let (fb_config, (depth, visual)) = match options.gl_config {
             Some(gl_config) => unsafe {
+                let display = xcb_connection.conn.get_raw_dpy();
+
+                // If there's no framebuffer config with an alpha channel we'll fall back to an
+                // opaque window
                 platform::GlContext::get_fb_config_and_visual(
-                    xcb_connection.conn.get_raw_dpy(),
-                    gl_config,
+                    display,
+                    gl_config.clone(),
+                    options.transparent,
                 )
+                .or_else(|_| {
+                    platform::GlContext::get_fb_config_and_visual(display, gl_config, false)
+                })
                 .map(|(fb_config, window_config)| {
                     (Some(fb_config), (window_config.depth, window_config.visual))
                 })
@@ -230,6 +374,10 @@ impl<'a> Window<'a> {
         #[cfg(not(feature = "opengl"))]
         let (depth, visual) = create_default_config();
 
+        // Transparency needs a 32-bit ARGB visual. Without one the window is opaque, and the window
+        // handler is told that transparency is not available.
+        let transparent = options.transparent && depth == 32;
+
         // For this 32-bith depth to work, you also need to define a color map and set a border
         // pixel: https://cgit.freedesktop.org/xorg/xserver/tree/dix/window.c#n818
         let colormap = xcb_connection.conn.generate_id();
@@ -241,6 +389,32 @@ impl<'a> Window<'a> {
             visual,
         );
 
+        let mut window_attributes = vec![
+            (
+                xcb::CW_EVENT_MASK,
+                xcb::EVENT_MASK_EXPOSURE
+                    | xcb::EVENT_MASK_POINTER_MOTION
+                    | xcb::EVENT_MASK_BUTTON_PRESS
+                    | xcb::EVENT_MASK_BUTTON_RELEASE
+                    | xcb::EVENT_MASK_KEY_PRESS
+                    | xcb::EVENT_MASK_KEY_RELEASE
+                    | xcb::EVENT_MASK_STRUCTURE_NOTIFY
+                    | xcb::EVENT_MASK_ENTER_WINDOW
+                    | xcb::EVENT_MASK_LEAVE_WINDOW
+                    | xcb::EVENT_MASK_FOCUS_CHANGE
+                    | xcb::EVENT_MASK_VISIBILITY_CHANGE
+                    | xcb::EVENT_MASK_PROPERTY_CHANGE,
+            ),
+            // As mentioned above, these two values are needed to be able to create a window
+            // with a depth of 32-bits when the parent window has a different depth
+            (xcb::CW_COLORMAP, colormap),
+            (xcb::CW_BORDER_PIXEL, 0),
+        ];
+        if transparent {
+            // Anything that hasn't been drawn to yet will be fully transparent
+            window_attributes.push((xcb::CW_BACK_PIXEL, 0));
+        }
+
         let window_id = xcb_connection.conn.generate_id();
         xcb::create_window_checked(
             &xcb_connection.conn,
@@ -254,24 +428,7 @@ impl<'a> Window<'a> {
             0,                                         // window border
             xcb::WINDOW_CLASS_INPUT_OUTPUT as u16,
             visual,
-            &[
-                (
-                    xcb::CW_EVENT_MASK,
-                    xcb::EVENT_MASK_EXPOSURE
-                        | xcb::EVENT_MASK_POINTER_MOTION
-                        | xcb::EVENT_MASK_BUTTON_PRESS
-                        | xcb::EVENT_MASK_BUTTON_RELEASE
-                        | xcb::EVENT_MASK_KEY_PRESS
-                        | xcb::EVENT_MASK_KEY_RELEASE
-                        | xcb::EVENT_MASK_STRUCTURE_NOTIFY
-                        | xcb::EVENT_MASK_ENTER_WINDOW
-                        | xcb::EVENT_MASK_LEAVE_WINDOW,
-                ),
-                // As mentioned above, these two values are needed to be able to create a window
-                // with a depth of 32-bits when the parent window has a different depth
-                (xcb::CW_COLORMAP, colormap),
-                (xcb::CW_BORDER_PIXEL, 0),
-            ],
+            &window_attributes,
         )
         .request_check()
         .unwrap();
@@ -301,6 +458,65 @@ impl<'a> Window<'a> {
             );
         }
 
+        // We'll be notified when a compositing manager starts or stops so we can tell the window
+        // handler whether transparency is actually available. If the window could not be made
+        // transparent we'll always report that there's no compositing manager.
+        let compositor_selection = if options.transparent && !transparent {
+            None
+        } else {
+            xcb_connection.get_compositor_selection()
+        };
+        let xfixes_event_base = compositor_selection
+            .and_then(|selection| xcb_connection.watch_selection_owner(window_id, selection));
+        let is_composited = compositor_selection
+            .is_some_and(|selection| xcb_connection.selection_has_owner(selection));
+
+        let randr_event_base = match options.scale {
+            WindowScalePolicy::ScaleFactor(_) => None,
+            _ => xcb_connection.watch_scaling_changes(),
+        };
+
+        // XInput 2.1 motion events include scroll valuators with more precise scroll deltas than the
+        // core scroll wheel button events. Selecting these motion events means that the X server
+        // won't send us core motion events for the window anymore. Similarly, selecting touch
+        // events means that the X server won't emulate mouse events for touches anymore.
+        let valuators = match &xcb_connection.xinput {
+            Some(xinput) => {
+                let mut events = 0;
+                if xinput.supports_smooth_scrolling() {
+                    events |= x11::xinput2::XI_MotionMask | x11::xinput2::XI_DeviceChangedMask;
+                }
+                if options.touch != TouchPolicy::MouseOnly && xinput.supports_touch() {
+                    // The touch pressure valuators also change with the device
+                    events |= x11::xinput2::XI_TouchBeginMask
+                        | x11::xinput2::XI_TouchUpdateMask
+                        | x11::xinput2::XI_TouchEndMask
+                        | x11::xinput2::XI_DeviceChangedMask;
+                }
+
+                if events != 0 {
+                    xinput.select_events(&xcb_connection, window_id, events);
+                    xinput.query_valuators(&xcb_connection)
+                } else {
+                    DeviceValuators::new()
+                }
+            }
+            None => DeviceValuators::new(),
+        };
+
+        // Hosts usually hide embedded windows by unmapping their parent window rather than the
+        // embedded window itself, so we'll also need to know when that happens. Hosts that
+        // implement XEmbed will also manage our keyboard focus.
+        if parent.is_some() {
+            xembed::set_xembed_info(&xcb_connection, window_id);
+
+            xcb::change_window_attributes(
+                &xcb_connection.conn,
+                parent_id,
+                &[(xcb::CW_EVENT_MASK, xcb::EVENT_MASK_STRUCTURE_NOTIFY)],
+            );
+        }
+
         xcb_connection.conn.flush();
 
         // TODO: These APIs could use a couple tweaks now that everything is internal and there is
@@ -320,31 +536,74 @@ impl<'a> Window<'a> {
         });
 
         #[cfg(feature = "vulkan")]
-        let vk_context = {
+        let vk_context = options.vk_config.map(|vk_config| {
             use std::ffi::c_ulong;
 
             let window = window_id as c_ulong;
             let display = xcb_connection.conn.get_raw_dpy();
 
-            let context = unsafe { platform::VkContext::create(window, display) }
-                .expect("Could not create Vulkan context");
-            Some(VkContext::new(context))
-         };
+            unsafe {
+                vk_platform::VkContext::create(
+                    window,
+                    display,
+                    vk_config,
+                    window_info.physical_size(),
+                    transparent,
+                )
+            }
+            .map(VkContext::new)
+            .map_err(|error| {
+                log::error!("Could not create Vulkan context: {:?}", error);
+                error
+            })
+        });
 
         let mut inner = WindowInner {
             xcb_connection,
             window_id,
             window_info,
+            scale_policy: options.scale,
+            scale_source,
             visual_id: visual,
+            transparent,
             mouse_cursor: MouseCursor::default(),
+            cursor_grab: CursorGrabMode::None,
+            locked_cursor_position: None,
+            mouse_captured: false,
+            valuators,
+            touch_policy: options.touch,
+            last_xi_scroll_time: None,
+            active_touches: HashMap::new(),
 
             frame_interval: Duration::from_millis(15),
+            last_frame: Instant::now(),
+            timers: Timers::new(),
             event_loop_running: false,
             close_requested: false,
 
             new_physical_size: None,
+            position_changed: false,
+            outer_position: None,
+
+            is_visible: false,
+            is_occluded: false,
+            is_maximized: false,
+            is_minimized: false,
+            has_focus: false,
+            xembed: XEmbedState::new(),
+            is_parented: parent.is_some(),
             parent_handle,
 
+            compositor_selection,
+            xfixes_event_base,
+            is_composited,
+
+            randr_event_base,
+            scale_changed: false,
+            scale_monitor: None,
+
+            software_buffer: None,
+
             #[cfg(feature = "opengl")]
             gl_context,
             #[cfg(feature = "vulkan")]
@@ -358,10 +617,109 @@ impl<'a> Window<'a> {
         // Send an initial window resized event so the user is alerted of
         // the correct dpi scaling.
         handler.on_event(&mut window, Event::Window(WindowEvent::Resized(window_info)));
+        handler.on_event(&mut window, Event::Window(WindowEvent::CompositorChanged(is_composited)));
 
-        let _ = tx.send(Ok(SendableRwh(window.raw_window_handle())));
+        (inner, handler)
+    }
 
-        inner.run_event_loop(&mut handler);
+    pub fn current_monitor(&self) -> Option<MonitorInfo> {
+        let size = self.inner.window_info.physical_size();
+        let center = PhyPoint::new(size.width as i32 / 2, size.height as i32 / 2);
+        let position = self.inner.xcb_connection.translate_to_root(self.inner.window_id, center)?;
+
+        self.inner
+            .xcb_connection
+            .get_monitors()
+            .into_iter()
+            .find(|monitor| monitor.contains(position))
+    }
+
+    pub fn set_cursor_grab(&mut self, mode: CursorGrabMode) -> bool {
+        let inner = &mut *self.inner;
+        if inner.cursor_grab == mode {
+            return true;
+        }
+
+        inner.release_cursor_grab();
+
+        // The mouse capture uses the same pointer grab, so it needs to be restored without the
+        // confinement
+        let grabbed = match mode {
+            CursorGrabMode::None if inner.mouse_captured => inner.grab_pointer(false),
+            CursorGrabMode::None => {
+                xcb::ungrab_pointer(&inner.xcb_connection.conn, xcb::CURRENT_TIME);
+                true
+            }
+            CursorGrabMode::Confined | CursorGrabMode::Locked => inner.grab_pointer(true),
+        };
+        if !grabbed {
+            inner.xcb_connection.conn.flush();
+            return false;
+        }
+
+        if mode == CursorGrabMode::Locked {
+            inner.locked_cursor_position =
+                xcb::query_pointer(&inner.xcb_connection.conn, inner.window_id)
+                    .get_reply()
+                    .ok()
+                    .map(|reply| PhyPoint::new(reply.win_x() as i32, reply.win_y() as i32));
+
+            // Without XInput 2 the movement is computed from the cursor's distance to the center
+            // of the window, where it is moved back to after every motion event
+            if let Some(xinput) = &inner.xcb_connection.xinput {
+                let root = inner.xcb_connection.root_window();
+                xinput.select_events(&inner.xcb_connection, root, x11::xinput2::XI_RawMotionMask);
+            }
+            inner.warp_pointer(inner.window_center());
+        }
+
+        inner.cursor_grab = mode;
+        inner.xcb_connection.conn.flush();
+
+        true
+    }
+
+    pub fn capture_mouse(&mut self) -> bool {
+        let inner = &mut *self.inner;
+        if inner.mouse_captured {
+            return true;
+        }
+
+        // The cursor grab already keeps the pointer grabbed
+        if inner.cursor_grab == CursorGrabMode::None {
+            let grabbed = inner.grab_pointer(false);
+            inner.xcb_connection.conn.flush();
+            if !grabbed {
+                return false;
+            }
+        }
+
+        inner.mouse_captured = true;
+
+        true
+    }
+
+    pub fn release_mouse(&mut self) {
+        let inner = &mut *self.inner;
+        if !inner.mouse_captured {
+            return;
+        }
+
+        inner.mouse_captured = false;
+        if inner.cursor_grab == CursorGrabMode::None {
+            xcb::ungrab_pointer(&inner.xcb_connection.conn, xcb::CURRENT_TIME);
+            inner.xcb_connection.conn.flush();
+        }
+    }
+
+    pub fn set_cursor_position(&mut self, position: Point) {
+        let position = position.to_physical(&self.inner.window_info);
+        if self.inner.cursor_grab == CursorGrabMode::Locked {
+            self.inner.locked_cursor_position = Some(position);
+        } else {
+            self.inner.warp_pointer(position);
+            self.inner.xcb_connection.conn.flush();
+        }
     }
 
     pub fn set_mouse_cursor(&mut self, mouse_cursor: MouseCursor) {
@@ -384,10 +742,93 @@ impl<'a> Window<'a> {
         self.inner.mouse_cursor = mouse_cursor;
     }
 
+    pub fn present_pixels(&mut self, pixels: &[u32], size: PhySize, damage: &[PhyRect]) {
+        let inner = &mut *self.inner;
+        if inner.software_buffer.is_none() {
+            inner.software_buffer =
+                SoftwareBuffer::new(&inner.xcb_connection, inner.window_id, inner.transparent);
+        }
+
+        if let Some(software_buffer) = &mut inner.software_buffer {
+            software_buffer.present(&inner.xcb_connection, inner.window_id, pixels, size, damage);
+        }
+    }
+
     pub fn close(&mut self) {
         self.inner.close_requested = true;
     }
 
+    pub fn window_info(&self) -> WindowInfo {
+        self.inner.window_info
+    }
+
+    pub fn outer_position(&self) -> PhyPoint {
+        self.inner.get_outer_position().unwrap_or(PhyPoint::new(0, 0))
+    }
+
+    pub fn set_outer_position(&mut self, position: PhyPoint) {
+        let xcb_connection = &self.inner.xcb_connection;
+
+        // Embedded windows are positioned relative to their parent, while the window manager
+        // positions a top level window's frame at the requested root coordinates
+        let position = if self.inner.is_parented {
+            let parent = xcb::query_tree(&xcb_connection.conn, self.inner.window_id)
+                .get_reply()
+                .map(|reply| reply.parent());
+            let translated = parent.ok().and_then(|parent| {
+                xcb::translate_coordinates(
+                    &xcb_connection.conn,
+                    xcb_connection.root_window(),
+                    parent,
+                    position.x as i16,
+                    position.y as i16,
+                )
+                .get_reply()
+                .ok()
+            });
+
+            match translated {
+                Some(reply) => PhyPoint::new(reply.dst_x() as i32, reply.dst_y() as i32),
+                None => return,
+            }
+        } else {
+            position
+        };
+
+        xcb::configure_window(
+            &xcb_connection.conn,
+            self.inner.window_id,
+            &[
+                (xcb::CONFIG_WINDOW_X as u16, position.x as u32),
+                (xcb::CONFIG_WINDOW_Y as u16, position.y as u32),
+            ],
+        );
+        xcb_connection.conn.flush();
+
+        // This will trigger a `ConfigureNotify` event which will in turn notify the window handler
+        // about the new position
+    }
+
+    pub fn set_timer(&mut self, id: u32, interval: Duration, repeat: bool) {
+        self.inner.timers.set(id, interval, repeat);
+    }
+
+    pub fn cancel_timer(&mut self, id: u32) {
+        self.inner.timers.cancel(id);
+    }
+
+    pub fn has_focus(&self) -> bool {
+        self.inner.has_focus
+    }
+
+    pub fn scale_factor(&self) -> f64 {
+        self.inner.window_info.scale()
+    }
+
+    pub fn scale_source(&self) -> Option<ScaleSource> {
+        self.inner.scale_source
+    }
+
     pub fn resize(&mut self, size: Size) {
         let scaling = self.inner.window_info.scale();
         let new_window_info = WindowInfo::from_logical_size(size, scaling);
@@ -413,7 +854,17 @@ impl<'a> Window<'a> {
 
     #[cfg(feature = "vulkan")]
     pub fn vk_context(&self) -> Option<&crate::vk::VkContext> {
-        self.inner.vk_context.as_ref()
+        self.inner.vk_context.as_ref()?.as_ref().ok()
+    }
+
+    #[cfg(feature = "vulkan")]
+    pub fn vk_context_mut(&mut self) -> Option<&mut crate::vk::VkContext> {
+        self.inner.vk_context.as_mut()?.as_mut().ok()
+    }
+
+    #[cfg(feature = "vulkan")]
+    pub fn vk_context_error(&self) -> Option<&VkError> {
+        self.inner.vk_context.as_ref()?.as_ref().err()
     }
 
     fn find_visual_for_depth(screen: &StructPtr<xcb_screen_t>, depth: u8) -> Option<u32> {
@@ -440,14 +891,43 @@ impl WindowInner {
         // window is resized, and we need to batch those together and just send one resize event
         // when they've all been coalesced.
         self.new_physical_size = None;
+        self.position_changed = false;
+        self.scale_changed = false;
 
         while let Some(event) = self.xcb_connection.conn.poll_for_event() {
             self.handle_xcb_event(handler, event);
         }
 
-        if let Some(size) = self.new_physical_size.take() {
+        let new_physical_size = self.new_physical_size.take();
+        let new_scale = self.update_system_scaling();
+
+        if new_physical_size.is_some() || new_scale.is_some() {
+            let size = new_physical_size.unwrap_or_else(|| self.window_info.physical_size());
             self.window_info = WindowInfo::from_physical_size(size, self.window_info.scale());
 
+            if let Some(scale) = new_scale {
+                // The window keeps its logical size when the scale changes. The `ConfigureNotify`
+                // event caused by this won't result in another resize event since the window info
+                // will already be up to date.
+                self.window_info =
+                    WindowInfo::from_logical_size(self.window_info.logical_size(), scale);
+
+                xcb::configure_window(
+                    &self.xcb_connection.conn,
+                    self.window_id,
+                    &[
+                        (xcb::CONFIG_WINDOW_WIDTH as u16, self.window_info.physical_size().width),
+                        (xcb::CONFIG_WINDOW_HEIGHT as u16, self.window_info.physical_size().height),
+                    ],
+                );
+                self.xcb_connection.conn.flush();
+            }
+
+            #[cfg(feature = "vulkan")]
+            if let Some(Ok(vk_context)) = &mut self.vk_context {
+                vk_context.resize_swapchain(self.window_info.physical_size());
+            }
+
             let window_info = self.window_info;
 
             handler.on_event(
@@ -455,6 +935,10 @@ impl WindowInner {
                 Event::Window(WindowEvent::Resized(window_info)),
             );
         }
+
+        if self.position_changed {
+            self.handle_moved(handler);
+        }
     }
 
     // Event loop
@@ -462,68 +946,331 @@ impl WindowInner {
     // switch between poll() and select() (the latter of which is fine on *BSD), and we should do
     // the same.
     fn run_event_loop(&mut self, handler: &mut dyn WindowHandler) {
+        self.last_frame = Instant::now();
+        self.event_loop_running = true;
+
+        while self.event_loop_running {
+            self.pump_events(handler, None);
+        }
+    }
+
+    /// Run a single iteration of the event loop. This waits for new events until the next frame
+    /// is due, or until `timeout` has passed if that happens sooner.
+    fn pump_events(&mut self, handler: &mut dyn WindowHandler, timeout: Option<Duration>) {
         use nix::poll::*;
 
-        let xcb_fd = unsafe {
+        // We'll try to keep a consistent frame pace. If the last frame couldn't be processed in
+        // the expected frame time, this will throttle down to prevent multiple frames from
+        // being queued up. The conditional here is needed because event handling and frame
+        // drawing is interleaved. The `poll()` function below will wait until the next frame
+        // can be drawn, or until the window receives an event. We thus need to manually check
+        // if it's already time to draw a new frame.
+        let next_frame = self.last_frame + self.frame_interval;
+        if Instant::now() >= next_frame {
+            handler.on_frame(&mut crate::Window::new(Window { inner: self }));
+            self.last_frame = Instant::max(next_frame, Instant::now() - self.frame_interval);
+        }
+
+        self.handle_timers(handler);
+
+        let mut fds = [PollFd::new(self.xcb_fd(), PollFlags::POLLIN)];
+
+        // Check for any events in the internal buffers
+        // before going to sleep:
+        self.drain_xcb_events(handler);
+
+        let next_wakeup = match self.timers.next_deadline() {
+            Some(deadline) => Instant::min(next_frame, deadline),
+            None => next_frame,
+        };
+        let mut poll_timeout = next_wakeup.saturating_duration_since(Instant::now());
+        if let Some(timeout) = timeout {
+            poll_timeout = poll_timeout.min(timeout);
+        }
+
+        // The timeout is rounded up so we don't wake up just before a timer is due and then spin
+        // until it is
+        let poll_timeout_ms = poll_timeout.as_micros().div_ceil(1000);
+
+        // FIXME: handle errors
+        poll(&mut fds, poll_timeout_ms as i32).unwrap();
+
+        if let Some(revents) = fds[0].revents() {
+            if revents.contains(PollFlags::POLLERR) {
+                panic!("xcb connection poll error");
+            }
+
+            if revents.contains(PollFlags::POLLIN) {
+                self.drain_xcb_events(handler);
+            }
+        }
+
+        self.handle_close_requests(handler);
+    }
+
+    fn xcb_fd(&self) -> RawFd {
+        unsafe {
             let raw_conn = self.xcb_connection.conn.get_raw_conn();
             xcb::ffi::xcb_get_file_descriptor(raw_conn)
-        };
+        }
+    }
 
-        let mut last_frame = Instant::now();
-        self.event_loop_running = true;
+    /// Close the window if it's still open and drop its handler, for windows that aren't run by
+    /// [Self::run_event_loop].
+    fn shut_down(&mut self, mut handler: Box<dyn WindowHandler>) {
+        if self.event_loop_running {
+            self.handle_must_close(handler.as_mut());
+        }
 
-        while self.event_loop_running {
-            // We'll try to keep a consistent frame pace. If the last frame couldn't be processed in
-            // the expected frame time, this will throttle down to prevent multiple frames from
-            // being queued up. The conditional here is needed because event handling and frame
-            // drawing is interleaved. The `poll()` function below will wait until the next frame
-            // can be drawn, or until the window receives an event. We thus need to manually check
-            // if it's already time to draw a new frame.
-            let next_frame = last_frame + self.frame_interval;
-            if Instant::now() >= next_frame {
-                handler.on_frame(&mut crate::Window::new(Window { inner: self }));
-                last_frame = Instant::max(next_frame, Instant::now() - self.frame_interval);
-            }
-
-            let mut fds = [PollFd::new(xcb_fd, PollFlags::POLLIN)];
-
-            // Check for any events in the internal buffers
-            // before going to sleep:
-            self.drain_xcb_events(handler);
-
-            // FIXME: handle errors
-            poll(&mut fds, next_frame.duration_since(Instant::now()).subsec_millis() as i32)
-                .unwrap();
-
-            if let Some(revents) = fds[0].revents() {
-                if revents.contains(PollFlags::POLLERR) {
-                    panic!("xcb connection poll error");
-                }
+        // The handler may still own objects created from the Vulkan device, and the Vulkan surface
+        // needs to be destroyed while the X11 connection is still open
+        drop(handler);
+        #[cfg(feature = "vulkan")]
+        drop(self.vk_context.take());
+    }
 
-                if revents.contains(PollFlags::POLLIN) {
-                    self.drain_xcb_events(handler);
-                }
+    /// Actively grab the pointer so all pointer events are sent to this window, optionally
+    /// confining the cursor to the window. Returns `false` if the pointer could not be grabbed.
+    fn grab_pointer(&self, confine: bool) -> bool {
+        let event_mask = xcb::EVENT_MASK_BUTTON_PRESS
+            | xcb::EVENT_MASK_BUTTON_RELEASE
+            | xcb::EVENT_MASK_POINTER_MOTION
+            | xcb::EVENT_MASK_ENTER_WINDOW
+            | xcb::EVENT_MASK_LEAVE_WINDOW;
+
+        xcb::grab_pointer(
+            &self.xcb_connection.conn,
+            true,
+            self.window_id,
+            event_mask as u16,
+            xcb::GRAB_MODE_ASYNC as u8,
+            xcb::GRAB_MODE_ASYNC as u8,
+            if confine { self.window_id } else { xcb::NONE },
+            xcb::NONE,
+            xcb::CURRENT_TIME,
+        )
+        .get_reply()
+        .is_ok_and(|reply| reply.status() == xcb::GRAB_STATUS_SUCCESS as u8)
+    }
+
+    /// Undo the state changes from [CursorGrabMode::Locked], without releasing the pointer grab
+    /// itself.
+    fn release_cursor_grab(&mut self) {
+        if self.cursor_grab == CursorGrabMode::Locked {
+            if let Some(xinput) = &self.xcb_connection.xinput {
+                xinput.select_events(&self.xcb_connection, self.xcb_connection.root_window(), 0);
+            }
+            if let Some(position) = self.locked_cursor_position.take() {
+                self.warp_pointer(position);
             }
+        }
 
-            // Check if the parents's handle was dropped (such as when the host
-            // requested the window to close)
-            //
-            // FIXME: This will need to be changed from just setting an atomic to somehow
-            // synchronizing with the window being closed (using a synchronous channel, or
-            // by joining on the event loop thread).
-            if let Some(parent_handle) = &self.parent_handle {
-                if parent_handle.parent_did_drop() {
-                    self.handle_must_close(handler);
-                    self.close_requested = false;
+        self.cursor_grab = CursorGrabMode::None;
+    }
+
+    /// Called when a crossing event indicates that our pointer grab has ended, for instance because
+    /// the window got unmapped or another client grabbed the pointer.
+    fn handle_grab_broken(&mut self, handler: &mut dyn WindowHandler) {
+        self.release_cursor_grab();
+        self.xcb_connection.conn.flush();
+
+        if self.mouse_captured {
+            self.mouse_captured = false;
+
+            handler.on_event(
+                &mut crate::Window::new(Window { inner: self }),
+                Event::Mouse(MouseEvent::CaptureLost),
+            );
+        }
+    }
+
+    /// Move the mouse cursor to `position`, relative to the window.
+    fn warp_pointer(&self, position: PhyPoint) {
+        xcb::warp_pointer(
+            &self.xcb_connection.conn,
+            xcb::NONE,
+            self.window_id,
+            0,
+            0,
+            0,
+            0,
+            position.x as i16,
+            position.y as i16,
+        );
+    }
+
+    /// Where the cursor is kept while it's locked.
+    fn window_center(&self) -> PhyPoint {
+        let size = self.window_info.physical_size();
+        PhyPoint::new(size.width as i32 / 2, size.height as i32 / 2)
+    }
+
+    fn handle_cursor_moved(
+        &mut self, handler: &mut dyn WindowHandler, position: PhyPoint, modifiers: u16,
+    ) {
+        if self.cursor_grab == CursorGrabMode::Locked {
+            self.handle_locked_motion(handler, position);
+            return;
+        }
+
+        let logical_pos = position.to_logical(&self.window_info);
+        handler.on_event(
+            &mut crate::Window::new(Window { inner: self }),
+            Event::Mouse(MouseEvent::CursorMoved {
+                position: logical_pos,
+                modifiers: key_mods(modifiers),
+            }),
+        );
+    }
+
+    /// Handle an XInput motion event, which replaces the core motion events when smooth scrolling
+    /// is supported. Scrolling is reported as changes to the scroll valuators. Touchpads report
+    /// these changes in pixels, so they're sent as [ScrollDelta::Pixels].
+    fn handle_xi_motion(&mut self, handler: &mut dyn WindowHandler, event: DeviceEvent) {
+        let is_touchpad = self.valuators.touchpads.contains(&event.sourceid);
+        let mut scrolled = false;
+        let mut delta_x = 0.0;
+        let mut delta_y = 0.0;
+        for valuator in &mut self.valuators.scroll {
+            if valuator.deviceid != event.deviceid || valuator.increment == 0.0 {
+                continue;
+            }
+
+            if let Some(value) = event.valuator(valuator.number) {
+                scrolled = true;
+
+                if let Some(last_value) = valuator.last_value {
+                    // One increment corresponds to a single scroll wheel click. Positive values
+                    // scroll down and to the right.
+                    let delta = if is_touchpad {
+                        value - last_value
+                    } else {
+                        (value - last_value) / valuator.increment
+                    };
+                    if valuator.horizontal {
+                        delta_x += delta;
+                    } else {
+                        delta_y -= delta;
+                    }
                 }
+                valuator.last_value = Some(value);
             }
+        }
+
+        if delta_x != 0.0 || delta_y != 0.0 {
+            self.last_xi_scroll_time = Some(event.time);
+
+            let delta = if is_touchpad {
+                // The valuators are in physical pixels, like the pointer's position
+                let scale = self.window_info.scale();
+                ScrollDelta::Pixels { x: (delta_x / scale) as f32, y: (delta_y / scale) as f32 }
+            } else {
+                ScrollDelta::Lines { x: delta_x as f32, y: delta_y as f32 }
+            };
+            handler.on_event(
+                &mut crate::Window::new(Window { inner: self }),
+                Event::Mouse(MouseEvent::WheelScrolled {
+                    delta,
+                    modifiers: key_mods(event.modifiers),
+                }),
+            );
+        }
+
+        // Valuators 0 and 1 are the pointer's x and y axes
+        if !scrolled || event.valuator(0).is_some() || event.valuator(1).is_some() {
+            self.handle_cursor_moved(handler, event.position(), event.modifiers);
+        }
+
+        self.handle_pen_motion(handler, &event);
+    }
+
+    /// Send a [PenEvent] if `event` was generated by a pen. Pens also move the pointer, so this is
+    /// sent in addition to the mouse events.
+    fn handle_pen_motion(&mut self, handler: &mut dyn WindowHandler, event: &DeviceEvent) {
+        let pen = match self.valuators.pens.iter_mut().find(|pen| pen.sourceid == event.sourceid) {
+            Some(pen) => pen,
+            None => return,
+        };
+        pen.update(event);
+
+        let scale = self.window_info.scale();
+        let pen_event = PenEvent {
+            position: Point::new(event.x / scale, event.y / scale),
+            pressure: pen.pressure,
+            tilt_x: pen.tilt_x,
+            tilt_y: pen.tilt_y,
+            rotation: pen.rotation,
+            is_eraser: pen.is_eraser,
+            // The pen's tip and barrel buttons are reported as the first three pointer buttons
+            in_contact: event.button_pressed(1),
+            barrel_buttons: [event.button_pressed(2), event.button_pressed(3)],
+            modifiers: key_mods(event.modifiers),
+        };
+
+        handler.on_event(&mut crate::Window::new(Window { inner: self }), Event::Pen(pen_event));
+    }
+
+    /// Handle a motion event while the cursor is locked by moving the cursor back to the center of
+    /// the window.
+    fn handle_locked_motion(&mut self, handler: &mut dyn WindowHandler, position: PhyPoint) {
+        let center = self.window_center();
+        if position == center {
+            return;
+        }
+
+        // With XInput 2 the movement is reported through the raw motion events instead
+        if self.xcb_connection.xinput.is_none() {
+            let scale = self.window_info.scale();
+            let delta = Point::new(
+                (position.x - center.x) as f64 / scale,
+                (position.y - center.y) as f64 / scale,
+            );
+
+            handler.on_event(
+                &mut crate::Window::new(Window { inner: self }),
+                Event::Mouse(MouseEvent::RawMotion { delta }),
+            );
+        }
+
+        self.warp_pointer(center);
+        self.xcb_connection.conn.flush();
+    }
+
+    /// The time until the next timer set through [crate::Window::set_timer] is due, or `None` if
+    /// there are no timers.
+    fn next_timer_timeout(&self) -> Option<Duration> {
+        let deadline = self.timers.next_deadline()?;
+
+        Some(deadline.saturating_duration_since(Instant::now()))
+    }
 
-            // Check if the user has requested the window to close
-            if self.close_requested {
+    /// Send [Event::Timer] events for all timers that are due.
+    fn handle_timers(&mut self, handler: &mut dyn WindowHandler) {
+        let now = Instant::now();
+        while let Some(id) = self.timers.pop_expired(now) {
+            handler.on_event(&mut crate::Window::new(Window { inner: self }), Event::Timer(id));
+        }
+    }
+
+    fn handle_close_requests(&mut self, handler: &mut dyn WindowHandler) {
+        // Check if the parents's handle was dropped (such as when the host
+        // requested the window to close)
+        //
+        // FIXME: This will need to be changed from just setting an atomic to somehow
+        // synchronizing with the window being closed (using a synchronous channel, or
+        // by joining on the event loop thread).
+        if let Some(parent_handle) = &self.parent_handle {
+            if parent_handle.parent_did_drop() {
                 self.handle_must_close(handler);
                 self.close_requested = false;
             }
         }
+
+        // Check if the user has requested the window to close
+        if self.close_requested {
+            self.handle_must_close(handler);
+            self.close_requested = false;
+        }
     }
 
     fn handle_close_requested(&mut self, handler: &mut dyn WindowHandler) {
@@ -545,9 +1292,319 @@ impl WindowInner {
         self.event_loop_running = false;
     }
 
+    /// The position of the top left corner of the window's frame in root window coordinates. For
+    /// embedded windows this is simply the window's own position.
+    fn get_outer_position(&self) -> Option<PhyPoint> {
+        let position =
+            self.xcb_connection.translate_to_root(self.window_id, PhyPoint::new(0, 0))?;
+        if self.is_parented {
+            return Some(position);
+        }
+
+        let (left, top) = self.xcb_connection.get_frame_extents(self.window_id);
+
+        Some(PhyPoint::new(position.x - left, position.y - top))
+    }
+
+    fn handle_moved(&mut self, handler: &mut dyn WindowHandler) {
+        let outer_position = match self.get_outer_position() {
+            Some(outer_position) if Some(outer_position) != self.outer_position => outer_position,
+            _ => return,
+        };
+
+        self.outer_position = Some(outer_position);
+        handler.on_event(
+            &mut crate::Window::new(Window { inner: self }),
+            Event::Window(WindowEvent::Moved(outer_position)),
+        );
+    }
+
+    /// Embedded windows don't get the keyboard focus from the window manager when they're clicked
+    /// on, so we'll need to ask the host or take it ourselves. `time` is the time of the event that
+    /// caused this.
+    fn request_focus(&self, time: u32) {
+        if !self.is_parented {
+            return;
+        }
+
+        if let Some(embedder) = self.xembed.embedder {
+            xembed::send_message(&self.xcb_connection, embedder, XEMBED_REQUEST_FOCUS);
+            return;
+        }
+
+        // Without an embedder we'll take the focus ourselves, but only if the host's window already
+        // has it. Otherwise clicking on the window would steal the focus from other applications,
+        // or from a host window that isn't supposed to accept keyboard input.
+        let conn = &self.xcb_connection.conn;
+        let focus = match xcb::get_input_focus(conn).get_reply() {
+            Ok(reply) => reply.focus(),
+            Err(_) => return,
+        };
+        // These are not actual windows
+        if focus == xcb::NONE || focus == xcb::INPUT_FOCUS_POINTER_ROOT {
+            return;
+        }
+
+        let toplevel = self.xcb_connection.get_root_child(self.window_id);
+        if toplevel.is_none() || self.xcb_connection.get_root_child(focus) != toplevel {
+            return;
+        }
+
+        xcb::set_input_focus(conn, xcb::INPUT_FOCUS_PARENT as u8, self.window_id, time);
+        conn.flush();
+    }
+
+    fn handle_focus_changed(&mut self, handler: &mut dyn WindowHandler, has_focus: bool) {
+        if has_focus == self.has_focus {
+            return;
+        }
+
+        self.has_focus = has_focus;
+        handler.on_event(
+            &mut crate::Window::new(Window { inner: self }),
+            Event::Window(if has_focus { WindowEvent::Focused } else { WindowEvent::Unfocused }),
+        );
+    }
+
+    fn handle_map_state_changed(&mut self, handler: &mut dyn WindowHandler) {
+        // The window is only viewable if it and all of its ancestors are mapped
+        let is_visible = xcb::get_window_attributes(&self.xcb_connection.conn, self.window_id)
+            .get_reply()
+            .is_ok_and(|reply| reply.map_state() == xcb::MAP_STATE_VIEWABLE as u8);
+        if is_visible == self.is_visible {
+            return;
+        }
+
+        self.is_visible = is_visible;
+        handler.on_event(
+            &mut crate::Window::new(Window { inner: self }),
+            Event::Window(if is_visible { WindowEvent::Shown } else { WindowEvent::Hidden }),
+        );
+
+        // We won't receive the end events for touches on a window that is no longer viewable
+        if !is_visible {
+            self.cancel_touches(handler);
+        }
+    }
+
+    fn handle_touch(
+        &mut self, handler: &mut dyn WindowHandler, phase: TouchPhase, event: DeviceEvent,
+    ) {
+        if phase == TouchPhase::Started && !self.has_focus {
+            self.request_focus(event.time);
+        }
+
+        let scale = self.window_info.scale();
+        let position = Point::new(event.x / scale, event.y / scale);
+        let pressure = self
+            .valuators
+            .pressure
+            .iter()
+            .find(|valuator| valuator.sourceid == event.sourceid)
+            .and_then(|valuator| Some(valuator.normalize(event.valuator(valuator.number)?)));
+
+        let touch = match phase {
+            TouchPhase::Started => self
+                .active_touches
+                .entry(event.detail)
+                .or_insert(ActiveTouch { position, force: pressure }),
+            _ => match self.active_touches.get_mut(&event.detail) {
+                Some(touch) => touch,
+                // This touch was already cancelled
+                None => return,
+            },
+        };
+        touch.position = position;
+        // The pressure is only included when it changes
+        if pressure.is_some() {
+            touch.force = pressure;
+        }
+        let force = touch.force;
+
+        if phase == TouchPhase::Ended {
+            self.active_touches.remove(&event.detail);
+        }
+
+        handler.on_event(
+            &mut crate::Window::new(Window { inner: self }),
+            Event::Touch(TouchEvent { id: event.detail as u64, phase, position, force }),
+        );
+
+        // The X server marks one touch at a time as the one that would have been used for the
+        // pointer emulation if we hadn't selected touch events
+        let emulating_pointer = event.flags & x11::xinput2::XITouchEmulatingPointer as u32 != 0;
+        if self.touch_policy == TouchPolicy::TouchAndMouse && emulating_pointer {
+            self.handle_cursor_moved(handler, event.position(), event.modifiers);
+
+            let mouse_event = match phase {
+                TouchPhase::Started => MouseEvent::ButtonPressed {
+                    button: MouseButton::Left,
+                    modifiers: key_mods(event.modifiers),
+                },
+                TouchPhase::Ended => MouseEvent::ButtonReleased {
+                    button: MouseButton::Left,
+                    modifiers: key_mods(event.modifiers),
+                },
+                _ => return,
+            };
+            handler.on_event(
+                &mut crate::Window::new(Window { inner: self }),
+                Event::Mouse(mouse_event),
+            );
+        }
+    }
+
+    /// Send [TouchPhase::Cancelled] events for all active touches.
+    fn cancel_touches(&mut self, handler: &mut dyn WindowHandler) {
+        let touches: Vec<_> = self.active_touches.drain().collect();
+        for (id, touch) in touches {
+            handler.on_event(
+                &mut crate::Window::new(Window { inner: self }),
+                Event::Touch(TouchEvent {
+                    id: id as u64,
+                    phase: TouchPhase::Cancelled,
+                    position: touch.position,
+                    force: touch.force,
+                }),
+            );
+        }
+    }
+
+    fn handle_wm_state_changed(&mut self, handler: &mut dyn WindowHandler) {
+        let atoms = &self.xcb_connection.atoms;
+        let wm_state = self.xcb_connection.get_wm_state(self.window_id);
+        let has_state = |atom: Option<u32>| atom.is_some_and(|atom| wm_state.contains(&atom));
+
+        let is_maximized = has_state(atoms.net_wm_state_maximized_vert)
+            && has_state(atoms.net_wm_state_maximized_horz);
+        let is_minimized = has_state(atoms.net_wm_state_hidden);
+
+        if is_maximized != self.is_maximized {
+            self.is_maximized = is_maximized;
+            handler.on_event(
+                &mut crate::Window::new(Window { inner: self }),
+                Event::Window(WindowEvent::Maximized(is_maximized)),
+            );
+        }
+
+        if is_minimized != self.is_minimized {
+            self.is_minimized = is_minimized;
+            handler.on_event(
+                &mut crate::Window::new(Window { inner: self }),
+                Event::Window(WindowEvent::Minimized(is_minimized)),
+            );
+        }
+    }
+
+    /// Resolve the system scale factor again if it may have changed, either because
+    /// `scale_changed` is set or because the window's center moved to another monitor. Returns the
+    /// new scale factor if it differs from the current one.
+    fn update_system_scaling(&mut self) -> Option<f64> {
+        let follows_monitor = scale_sources(self.scale_policy)?.contains(&ScaleSource::MonitorDpi);
+        if !(self.scale_changed || self.position_changed && follows_monitor) {
+            return None;
+        }
+
+        let size = self.window_info.physical_size();
+        let center = PhyPoint::new(size.width as i32 / 2, size.height as i32 / 2);
+        let position = self.xcb_connection.translate_to_root(self.window_id, center)?;
+
+        if follows_monitor {
+            let monitor = self.xcb_connection.get_monitor_index(position);
+            if !self.scale_changed && monitor == self.scale_monitor {
+                return None;
+            }
+
+            self.scale_monitor = monitor;
+        }
+
+        let (scale, scale_source) =
+            resolve_scaling(&self.xcb_connection, self.scale_policy, position);
+        self.scale_source = scale_source;
+
+        Some(scale).filter(|&scale| scale != self.window_info.scale())
+    }
+
+    fn handle_compositor_changed(&mut self, handler: &mut dyn WindowHandler) {
+        let is_composited = self
+            .compositor_selection
+            .is_some_and(|selection| self.xcb_connection.selection_has_owner(selection));
+        if is_composited == self.is_composited {
+            return;
+        }
+
+        self.is_composited = is_composited;
+        handler.on_event(
+            &mut crate::Window::new(Window { inner: self }),
+            Event::Window(WindowEvent::CompositorChanged(is_composited)),
+        );
+    }
+
     fn handle_xcb_event(&mut self, handler: &mut dyn WindowHandler, event: xcb::GenericEvent) {
         let event_type = event.response_type() & !0x80;
 
+        // Extension events don't have fixed event types, so they can't be matched on below. The
+        // only selection we're watching is the compositing manager's.
+        if let Some(xfixes_event_base) = self.xfixes_event_base {
+            if event_type == xfixes_event_base + xcb::xfixes::SELECTION_NOTIFY {
+                self.handle_compositor_changed(handler);
+                return;
+            }
+        }
+        if let Some(software_buffer) = &mut self.software_buffer {
+            if software_buffer.handle_event(event_type) {
+                return;
+            }
+        }
+        if let Some(randr_event_base) = self.randr_event_base {
+            if event_type == randr_event_base + xcb::randr::SCREEN_CHANGE_NOTIFY
+                || event_type == randr_event_base + xcb::randr::NOTIFY
+            {
+                self.xcb_connection.invalidate_scaling_cache();
+                self.scale_changed = true;
+                return;
+            }
+        }
+
+        if let Some(xinput) = &self.xcb_connection.xinput {
+            if let Some((xi_event_type, data)) = xinput.decode_event(&event) {
+                match xi_event_type {
+                    x11::xinput2::XI_RawMotion if self.cursor_grab == CursorGrabMode::Locked => {
+                        let (x, y) = xinput::raw_motion_delta(data);
+                        let scale = self.window_info.scale();
+                        handler.on_event(
+                            &mut crate::Window::new(Window { inner: self }),
+                            Event::Mouse(MouseEvent::RawMotion {
+                                delta: Point::new(x / scale, y / scale),
+                            }),
+                        );
+                    }
+                    x11::xinput2::XI_Motion => {
+                        self.handle_xi_motion(handler, DeviceEvent::parse(data));
+                    }
+                    x11::xinput2::XI_TouchBegin => {
+                        self.handle_touch(handler, TouchPhase::Started, DeviceEvent::parse(data));
+                    }
+                    x11::xinput2::XI_TouchUpdate => {
+                        self.handle_touch(handler, TouchPhase::Moved, DeviceEvent::parse(data));
+                    }
+                    x11::xinput2::XI_TouchEnd => {
+                        self.handle_touch(handler, TouchPhase::Ended, DeviceEvent::parse(data));
+                    }
+                    x11::xinput2::XI_DeviceChanged => {
+                        // The master device's valuators change when a different physical device
+                        // is used
+                        if let Some(xinput) = &self.xcb_connection.xinput {
+                            self.valuators = xinput.query_valuators(&self.xcb_connection);
+                        }
+                    }
+                    _ => (),
+                }
+
+                return;
+            }
+        }
+
         // For all of the keyboard and mouse events, you can fetch
         // `x`, `y`, `detail`, and `state`.
         // - `x` and `y` are the position inside the window where the cursor currently is
@@ -579,6 +1636,21 @@ impl WindowInner {
                 let data = event.data().data;
                 let (_, data32, _) = unsafe { data.align_to::<u32>() };
 
+                // When embedded through XEmbed, the host tells us when we gain or lose focus
+                if Some(event.type_()) == self.xcb_connection.atoms.xembed {
+                    match data32[1] {
+                        XEMBED_EMBEDDED_NOTIFY => self.xembed.embedder = Some(data32[3]),
+                        XEMBED_WINDOW_ACTIVATE => self.xembed.window_active = true,
+                        XEMBED_WINDOW_DEACTIVATE => self.xembed.window_active = false,
+                        XEMBED_FOCUS_IN => self.xembed.focused = true,
+                        XEMBED_FOCUS_OUT => self.xembed.focused = false,
+                        _ => return,
+                    }
+
+                    self.handle_focus_changed(handler, self.xembed.has_focus());
+                    return;
+                }
+
                 let wm_delete_window =
                     self.xcb_connection.atoms.wm_delete_window.unwrap_or(xcb::NONE);
 
@@ -587,15 +1659,70 @@ impl WindowInner {
                 }
             }
 
+            xcb::FOCUS_IN | xcb::FOCUS_OUT => {
+                let event = unsafe { xcb::cast_event::<xcb::FocusInEvent>(&event) };
+
+                // Keyboard grabs (for instance while a window manager's window switcher is open)
+                // temporarily move the focus without the user actually focusing another window,
+                // and `NotifyPointer` events are only sent for the window under the pointer
+                let mode = event.mode() as u32;
+                if mode == xcb::NOTIFY_MODE_GRAB
+                    || mode == xcb::NOTIFY_MODE_UNGRAB
+                    || event.detail() as u32 == xcb::NOTIFY_DETAIL_POINTER
+                {
+                    return;
+                }
+
+                self.handle_focus_changed(handler, event_type == xcb::FOCUS_IN);
+            }
+
             xcb::CONFIGURE_NOTIFY => {
                 let event = unsafe { xcb::cast_event::<xcb::ConfigureNotifyEvent>(&event) };
 
-                let new_physical_size = PhySize::new(event.width() as u32, event.height() as u32);
+                // This may also be the parent window of an embedded window being reconfigured
+                if event.window() == self.window_id {
+                    let new_physical_size =
+                        PhySize::new(event.width() as u32, event.height() as u32);
+
+                    if self.new_physical_size.is_some()
+                        || new_physical_size != self.window_info.physical_size()
+                    {
+                        self.new_physical_size = Some(new_physical_size);
+                    }
+                }
+
+                // The window may have been moved, possibly to another monitor
+                self.position_changed = true;
+            }
 
-                if self.new_physical_size.is_some()
-                    || new_physical_size != self.window_info.physical_size()
+            xcb::PROPERTY_NOTIFY => {
+                let event = unsafe { xcb::cast_event::<xcb::PropertyNotifyEvent>(&event) };
+
+                // This contains the `Xft.dpi` setting
+                if event.atom() == xcb::ATOM_RESOURCE_MANAGER {
+                    self.xcb_connection.invalidate_scaling_cache();
+                    self.scale_changed = true;
+                } else if event.window() == self.window_id
+                    && Some(event.atom()) == self.xcb_connection.atoms.net_wm_state
                 {
-                    self.new_physical_size = Some(new_physical_size);
+                    self.handle_wm_state_changed(handler);
+                }
+            }
+
+            xcb::MAP_NOTIFY | xcb::UNMAP_NOTIFY => {
+                self.handle_map_state_changed(handler);
+            }
+
+            xcb::VISIBILITY_NOTIFY => {
+                let event = unsafe { xcb::cast_event::<xcb::VisibilityNotifyEvent>(&event) };
+
+                let is_occluded = event.state() == xcb::VISIBILITY_FULLY_OBSCURED as u8;
+                if is_occluded != self.is_occluded {
+                    self.is_occluded = is_occluded;
+                    handler.on_event(
+                        &mut crate::Window::new(Window { inner: self }),
+                        Event::Window(WindowEvent::Occluded(is_occluded)),
+                    );
                 }
             }
 
@@ -609,26 +1736,28 @@ impl WindowInner {
                 if detail != 4 && detail != 5 {
                     let physical_pos =
                         PhyPoint::new(event.event_x() as i32, event.event_y() as i32);
-                    let logical_pos = physical_pos.to_logical(&self.window_info);
-
-                    handler.on_event(
-                        &mut crate::Window::new(Window { inner: self }),
-                        Event::Mouse(MouseEvent::CursorMoved {
-                            position: logical_pos,
-                            modifiers: key_mods(event.state()),
-                        }),
-                    );
+                    self.handle_cursor_moved(handler, physical_pos, event.state());
                 }
             }
 
             xcb::ENTER_NOTIFY => {
+                let event = unsafe { xcb::cast_event::<xcb::EnterNotifyEvent>(&event) };
+                // Our own grabs generate an enter event with the grab mode if the pointer was
+                // outside of the window, but an ungrab we didn't ask for means the grab was broken
+                if event.mode() == xcb::NOTIFY_MODE_UNGRAB as u8 {
+                    self.handle_grab_broken(handler);
+                }
+
+                for valuator in &mut self.valuators.scroll {
+                    valuator.last_value = None;
+                }
+
                 handler.on_event(
                     &mut crate::Window::new(Window { inner: self }),
                     Event::Mouse(MouseEvent::CursorEntered),
                 );
                 // since no `MOTION_NOTIFY` event is generated when `ENTER_NOTIFY` is generated,
                 // we generate a CursorMoved as well, so the mouse position from here isn't lost
-                let event = unsafe { xcb::cast_event::<xcb::EnterNotifyEvent>(&event) };
                 let physical_pos = PhyPoint::new(event.event_x() as i32, event.event_y() as i32);
                 let logical_pos = physical_pos.to_logical(&self.window_info);
                 handler.on_event(
@@ -641,6 +1770,13 @@ impl WindowInner {
             }
 
             xcb::LEAVE_NOTIFY => {
+                let event = unsafe { xcb::cast_event::<xcb::LeaveNotifyEvent>(&event) };
+                if event.mode() == xcb::NOTIFY_MODE_GRAB as u8
+                    || event.mode() == xcb::NOTIFY_MODE_UNGRAB as u8
+                {
+                    self.handle_grab_broken(handler);
+                }
+
                 handler.on_event(
                     &mut crate::Window::new(Window { inner: self }),
                     Event::Mouse(MouseEvent::CursorLeft),
@@ -651,7 +1787,14 @@ impl WindowInner {
                 let event = unsafe { xcb::cast_event::<xcb::ButtonPressEvent>(&event) };
                 let detail = event.detail();
 
+                if !self.has_focus {
+                    self.request_focus(event.time());
+                }
+
                 match detail {
+                    // The X server also emulates these button events for smooth scrolling, which
+                    // we've already handled
+                    4..=7 if self.last_xi_scroll_time == Some(event.time()) => (),
                     4..=7 => {
                         handler.on_event(
                             &mut crate::Window::new(Window { inner: self }),
@@ -722,6 +1865,142 @@ impl WindowInner {
     }
 }
 
+/// A parented window that lives on the host's thread, see
+/// [crate::Window::open_parented_embedded].
+pub struct EmbeddedWindow {
+    inner: WindowInner,
+    /// Only `None` while the window is being dropped.
+    handler: Option<Box<dyn WindowHandler>>,
+}
+
+impl EmbeddedWindow {
+    pub fn pump(&mut self) {
+        if let Some(handler) = &mut self.handler {
+            if self.inner.event_loop_running {
+                self.inner.handle_timers(handler.as_mut());
+                self.inner.drain_xcb_events(handler.as_mut());
+                self.inner.handle_close_requests(handler.as_mut());
+            }
+        }
+    }
+
+    pub fn on_timer(&mut self) {
+        if let Some(handler) = &mut self.handler {
+            if self.inner.event_loop_running {
+                handler.on_frame(&mut crate::Window::new(Window { inner: &mut self.inner }));
+
+                self.inner.handle_timers(handler.as_mut());
+                self.inner.drain_xcb_events(handler.as_mut());
+                self.inner.handle_close_requests(handler.as_mut());
+            }
+        }
+    }
+
+    pub fn frame_interval(&self) -> Duration {
+        self.inner.frame_interval
+    }
+
+    pub fn next_timeout(&self) -> Option<Duration> {
+        if self.inner.event_loop_running {
+            self.inner.next_timer_timeout()
+        } else {
+            None
+        }
+    }
+
+    pub fn is_open(&self) -> bool {
+        self.inner.event_loop_running
+    }
+}
+
+impl AsRawFd for EmbeddedWindow {
+    fn as_raw_fd(&self) -> RawFd {
+        self.inner.xcb_fd()
+    }
+}
+
+unsafe impl HasRawWindowHandle for EmbeddedWindow {
+    fn raw_window_handle(&self) -> RawWindowHandle {
+        let mut handle = XlibWindowHandle::empty();
+
+        handle.window = self.inner.window_id.into();
+        handle.visual_id = self.inner.visual_id.into();
+
+        RawWindowHandle::Xlib(handle)
+    }
+}
+
+impl Drop for EmbeddedWindow {
+    fn drop(&mut self) {
+        if let Some(handler) = self.handler.take() {
+            self.inner.shut_down(handler);
+        }
+    }
+}
+
+/// A top level window whose event loop is run by calling [Self::pump_events], see
+/// [crate::Window::open_with_event_loop].
+pub struct EventLoop {
+    inner: WindowInner,
+    /// Only `None` while the window is being dropped.
+    handler: Option<Box<dyn WindowHandler>>,
+}
+
+impl EventLoop {
+    pub fn pump_events(&mut self, timeout: Option<Duration>) -> PumpStatus {
+        if let Some(handler) = &mut self.handler {
+            if self.inner.event_loop_running {
+                self.inner.pump_events(handler.as_mut(), timeout);
+            }
+        }
+
+        if self.inner.event_loop_running {
+            PumpStatus::Continue
+        } else {
+            PumpStatus::Exit
+        }
+    }
+
+    pub fn next_timeout(&self) -> Option<Duration> {
+        if !self.inner.event_loop_running {
+            return None;
+        }
+
+        let next_frame = self.inner.last_frame + self.inner.frame_interval;
+        let frame_timeout = next_frame.saturating_duration_since(Instant::now());
+
+        Some(match self.inner.next_timer_timeout() {
+            Some(timer_timeout) => timer_timeout.min(frame_timeout),
+            None => frame_timeout,
+        })
+    }
+}
+
+impl AsRawFd for EventLoop {
+    fn as_raw_fd(&self) -> RawFd {
+        self.inner.xcb_fd()
+    }
+}
+
+unsafe impl HasRawWindowHandle for EventLoop {
+    fn raw_window_handle(&self) -> RawWindowHandle {
+        let mut handle = XlibWindowHandle::empty();
+
+        handle.window = self.inner.window_id.into();
+        handle.visual_id = self.inner.visual_id.into();
+
+        RawWindowHandle::Xlib(handle)
+    }
+}
+
+impl Drop for EventLoop {
+    fn drop(&mut self) {
+        if let Some(handler) = self.handler.take() {
+            self.inner.shut_down(handler);
+        }
+    }
+}
+
 unsafe impl<'a> HasRawWindowHandle for Window<'a> {
     fn raw_window_handle(&self) -> RawWindowHandle {
         let mut handle = XlibWindowHandle::empty();
@@ -745,6 +2024,33 @@ unsafe impl<'a> HasRawDisplayHandle for Window<'a> {
     }
 }
 
+/// The sources the system scale factor is read from for `policy`, or `None` if the window uses a
+/// fixed scale factor.
+fn scale_sources(policy: WindowScalePolicy) -> Option<&'static [ScaleSource]> {
+    match policy {
+        WindowScalePolicy::SystemScaleFactor => Some(SYSTEM_SCALE_SOURCES),
+        WindowScalePolicy::ScaleFactor(_) => None,
+        WindowScalePolicy::Auto(auto_scale) => Some(auto_scale.sources),
+    }
+}
+
+/// Resolve the scale factor for a window whose center is at `position` according to `policy`.
+fn resolve_scaling(
+    xcb_connection: &XcbConnection, policy: WindowScalePolicy, position: PhyPoint,
+) -> (f64, Option<ScaleSource>) {
+    let sources = match policy {
+        WindowScalePolicy::ScaleFactor(scale) => return (scale, None),
+        _ => scale_sources(policy).unwrap_or_default(),
+    };
+
+    let (scale, scale_source) = match xcb_connection.get_scaling(sources, position) {
+        Some((scale, scale_source)) => (scale, Some(scale_source)),
+        None => (1.0, None),
+    };
+
+    (policy.apply_to_system_scale(scale).unwrap_or(scale), scale_source)
+}
+
 fn mouse_id(id: u8) -> MouseButton {
     match id {
         1 => MouseButton::Left,
@@ -759,3 +2065,10 @@ fn mouse_id(id: u8) -> MouseButton {
 pub fn copy_to_clipboard(_data: &str) {
     todo!()
 }
+
+pub fn monitors() -> Vec<MonitorInfo> {
+    match XcbConnection::new() {
+        Ok(xcb_connection) => xcb_connection.get_monitors(),
+        Err(_) => Vec::new(),
+    }
+}
@@ -1,16 +1,35 @@
+use std::cell::RefCell;
 use std::collections::HashMap;
 /// A very light abstraction around the XCB connection.
 ///
 /// Keeps track of the xcb connection itself and the xlib display ID that was used to connect.
 use std::ffi::{CStr, CString};
 
-use crate::MouseCursor;
+use xcb::randr;
+
+use crate::{MonitorInfo, MouseCursor, PhyPoint, PhySize, ScaleSource};
 
 use super::cursor;
+use super::xinput::XInput;
+
+/// The sources used for [crate::WindowScalePolicy::SystemScaleFactor], in order. `Xft.dpi` wins
+/// when it's set, and otherwise the scale factor follows the monitor the window is on. The
+/// screen's pixel density is only used when RandR can't tell us about the monitors.
+pub const SYSTEM_SCALE_SOURCES: &[ScaleSource] =
+    &[ScaleSource::XftDpi, ScaleSource::MonitorDpi, ScaleSource::ScreenDpi];
 
 pub(crate) struct Atoms {
     pub wm_protocols: Option<u32>,
     pub wm_delete_window: Option<u32>,
+    pub net_workarea: Option<u32>,
+    pub net_current_desktop: Option<u32>,
+    pub net_frame_extents: Option<u32>,
+    pub net_wm_state: Option<u32>,
+    pub net_wm_state_maximized_vert: Option<u32>,
+    pub net_wm_state_maximized_horz: Option<u32>,
+    pub net_wm_state_hidden: Option<u32>,
+    pub xembed: Option<u32>,
+    pub xembed_info: Option<u32>,
 }
 
 pub struct XcbConnection {
@@ -18,25 +37,42 @@ pub struct XcbConnection {
     pub xlib_display: i32,
 
     pub(crate) atoms: Atoms,
+    /// `None` if the X server does not support XInput 2.
+    pub(super) xinput: Option<XInput>,
 
     pub(super) cursor_cache: HashMap<MouseCursor, u32>,
+    scaling_cache: RefCell<ScalingCache>,
+}
+
+/// The values [XcbConnection::get_scaling] needs from the X server. These are cached since the
+/// scale factor is resolved again whenever the window moves to another monitor. The cache is
+/// cleared with [XcbConnection::invalidate_scaling_cache].
+#[derive(Default)]
+struct ScalingCache {
+    xft: Option<Option<f64>>,
+    monitors: Option<Vec<RandrMonitor>>,
 }
 
 macro_rules! intern_atoms {
-    ($conn:expr, $( $name:ident ),+ ) => {{
+    ($conn:expr, $only_if_exists:literal, $( $name:ident ),+ ) => {{
         $(
             #[allow(non_snake_case)]
-            let $name = xcb::intern_atom($conn, true, stringify!($name));
+            let $name = xcb::intern_atom($conn, $only_if_exists, stringify!($name));
         )+
 
         // splitting request and reply to improve throughput
 
         (
+            // `only_if_exists` atoms that don't exist come back as `None`
             $( $name.get_reply()
                 .map(|r| r.atom())
-                .ok()),+
+                .ok()
+                .filter(|&atom| atom != xcb::NONE)),+
         )
     }};
+    ($conn:expr, $( $name:ident ),+ ) => {
+        intern_atoms!($conn, true, $( $name ),+)
+    };
 }
 
 impl XcbConnection {
@@ -46,68 +82,218 @@ impl XcbConnection {
         conn.set_event_queue_owner(xcb::base::EventQueueOwner::Xcb);
 
         let (wm_protocols, wm_delete_window) = intern_atoms!(&conn, WM_PROTOCOLS, WM_DELETE_WINDOW);
+        // We set these on our own window, so they're created if nobody else has interned them yet
+        let (xembed, xembed_info) = intern_atoms!(&conn, false, _XEMBED, _XEMBED_INFO);
+        let (net_workarea, net_current_desktop, net_frame_extents) =
+            intern_atoms!(&conn, _NET_WORKAREA, _NET_CURRENT_DESKTOP, _NET_FRAME_EXTENTS);
+        let (
+            net_wm_state,
+            net_wm_state_maximized_vert,
+            net_wm_state_maximized_horz,
+            net_wm_state_hidden,
+        ) = intern_atoms!(
+            &conn,
+            _NET_WM_STATE,
+            _NET_WM_STATE_MAXIMIZED_VERT,
+            _NET_WM_STATE_MAXIMIZED_HORZ,
+            _NET_WM_STATE_HIDDEN
+        );
+
+        let xinput = XInput::new(&conn);
 
         Ok(Self {
             conn,
             xlib_display,
 
-            atoms: Atoms { wm_protocols, wm_delete_window },
+            atoms: Atoms {
+                wm_protocols,
+                wm_delete_window,
+                net_workarea,
+                net_current_desktop,
+                net_frame_extents,
+                net_wm_state,
+                net_wm_state_maximized_vert,
+                net_wm_state_maximized_horz,
+                net_wm_state_hidden,
+                xembed,
+                xembed_info,
+            },
+            xinput,
 
             cursor_cache: HashMap::new(),
+            scaling_cache: RefCell::new(ScalingCache::default()),
         })
     }
 
-    // Try to get the scaling with this function first.
-    // If this gives you `None`, fall back to `get_scaling_screen_dimensions`.
-    // If neither work, I guess just assume 96.0 and don't do any scaling.
-    fn get_scaling_xft(&self) -> Option<f64> {
-        use x11::xlib::{
-            XResourceManagerString, XrmDestroyDatabase, XrmGetResource, XrmGetStringDatabase,
-            XrmValue,
+    /// The root window of our screen.
+    pub fn root_window(&self) -> u32 {
+        let setup = self.conn.get_setup();
+        let screen = setup.roots().nth(self.xlib_display as usize).unwrap();
+
+        screen.root()
+    }
+
+    /// Convert `position` relative to `window` to root window coordinates.
+    pub fn translate_to_root(&self, window: u32, position: PhyPoint) -> Option<PhyPoint> {
+        let reply = xcb::translate_coordinates(
+            &self.conn,
+            window,
+            self.root_window(),
+            position.x as i16,
+            position.y as i16,
+        )
+        .get_reply()
+        .ok()?;
+
+        Some(PhyPoint::new(reply.dst_x() as i32, reply.dst_y() as i32))
+    }
+
+    /// The child of the root window that contains `window`. For top level windows this is usually
+    /// the window manager's frame.
+    pub fn get_root_child(&self, window: u32) -> Option<u32> {
+        let mut window = window;
+        loop {
+            let parent = xcb::query_tree(&self.conn, window).get_reply().ok()?.parent();
+            if parent == xcb::NONE {
+                // `window` is the root window itself
+                return None;
+            }
+            if parent == self.root_window() {
+                return Some(window);
+            }
+
+            window = parent;
+        }
+    }
+
+    /// The size of the window manager's decorations to the left of and above `window`, taken from
+    /// `_NET_FRAME_EXTENTS`. This is zero for undecorated windows.
+    pub fn get_frame_extents(&self, window: u32) -> (i32, i32) {
+        let extents = self.atoms.net_frame_extents.and_then(|net_frame_extents| {
+            xcb::get_property(
+                &self.conn,
+                false,
+                window,
+                net_frame_extents,
+                xcb::ATOM_CARDINAL,
+                0,
+                4,
+            )
+            .get_reply()
+            .ok()
+        });
+
+        // The property contains the left, right, top, and bottom extents
+        match extents.as_ref().map(|reply| reply.value::<u32>()) {
+            Some(&[left, _, top, _]) => (left as i32, top as i32),
+            _ => (0, 0),
+        }
+    }
+
+    /// The atoms in `window`'s `_NET_WM_STATE` property, which the window manager uses to indicate
+    /// whether the window is maximized, minimized, and so on.
+    pub fn get_wm_state(&self, window: u32) -> Vec<u32> {
+        let net_wm_state = match self.atoms.net_wm_state {
+            Some(net_wm_state) => net_wm_state,
+            None => return Vec::new(),
         };
 
-        let display = self.conn.get_raw_dpy();
+        xcb::get_property(&self.conn, false, window, net_wm_state, xcb::ATOM_ATOM, 0, 1024)
+            .get_reply()
+            .map(|reply| reply.value::<u32>().to_vec())
+            .unwrap_or_default()
+    }
+
+    // Reads `Xft.dpi`, see `ScaleSource::XftDpi`.
+    fn read_scaling_xft(&self) -> Option<f64> {
+        use x11::xlib::{XrmDestroyDatabase, XrmGetResource, XrmGetStringDatabase, XrmValue};
+
+        // Xlib only reads the `RESOURCE_MANAGER` property once when connecting, so we need to
+        // fetch it ourselves to pick up changes made while the window is open
+        let reply = xcb::get_property(
+            &self.conn,
+            false,
+            self.root_window(),
+            xcb::ATOM_RESOURCE_MANAGER,
+            xcb::ATOM_STRING,
+            0,
+            u32::MAX / 4,
+        )
+        .get_reply()
+        .ok()?;
+        let rms = CString::new(reply.value::<u8>()).ok()?;
+
         unsafe {
-            let rms = XResourceManagerString(display);
-            if !rms.is_null() {
-                let db = XrmGetStringDatabase(rms);
-                if !db.is_null() {
-                    let mut value = XrmValue { size: 0, addr: std::ptr::null_mut() };
-
-                    let mut value_type: *mut std::os::raw::c_char = std::ptr::null_mut();
-                    let name_c_str = CString::new("Xft.dpi").unwrap();
-                    let c_str = CString::new("Xft.Dpi").unwrap();
-
-                    let dpi = if XrmGetResource(
-                        db,
-                        name_c_str.as_ptr(),
-                        c_str.as_ptr(),
-                        &mut value_type,
-                        &mut value,
-                    ) != 0
-                        && !value.addr.is_null()
-                    {
-                        let value_addr: &CStr = CStr::from_ptr(value.addr);
-                        value_addr.to_str().ok();
-                        let value_str = value_addr.to_str().ok()?;
-                        let value_f64: f64 = value_str.parse().ok()?;
-                        let dpi_to_scale = value_f64 / 96.0;
-                        Some(dpi_to_scale)
-                    } else {
-                        None
-                    };
-                    XrmDestroyDatabase(db);
-
-                    return dpi;
-                }
+            let db = XrmGetStringDatabase(rms.as_ptr());
+            if !db.is_null() {
+                let mut value = XrmValue { size: 0, addr: std::ptr::null_mut() };
+
+                let mut value_type: *mut std::os::raw::c_char = std::ptr::null_mut();
+                let name_c_str = CString::new("Xft.dpi").unwrap();
+                let c_str = CString::new("Xft.Dpi").unwrap();
+
+                let dpi = if XrmGetResource(
+                    db,
+                    name_c_str.as_ptr(),
+                    c_str.as_ptr(),
+                    &mut value_type,
+                    &mut value,
+                ) != 0
+                    && !value.addr.is_null()
+                {
+                    let value_addr: &CStr = CStr::from_ptr(value.addr);
+                    let value_str = value_addr.to_str().ok()?;
+                    let value_f64: f64 = value_str.parse().ok()?;
+                    let dpi_to_scale = value_f64 / 96.0;
+                    Some(dpi_to_scale)
+                } else {
+                    None
+                };
+                XrmDestroyDatabase(db);
+
+                return dpi;
             }
         }
         None
     }
 
-    // Try to get the scaling with `get_scaling_xft` first.
-    // Only use this function as a fallback.
-    // If neither work, I guess just assume 96.0 and don't do any scaling.
+    /// Like [Self::read_scaling_xft], but cached.
+    fn get_scaling_xft(&self) -> Option<f64> {
+        *self.scaling_cache.borrow_mut().xft.get_or_insert_with(|| self.read_scaling_xft())
+    }
+
+    // This computes the DPI of the monitor showing `position` (in root window coordinates) from
+    // its physical size, so windows moved between monitors with different pixel densities will
+    // get different scales.
+    fn get_scaling_monitor(&self, position: PhyPoint) -> Option<f64> {
+        self.with_cached_monitors(|monitors| {
+            monitors
+                .iter()
+                .find(|monitor| monitor.contains(position))
+                .and_then(RandrMonitor::get_scaling)
+        })
+    }
+
+    /// The index of the RandR monitor showing `position`, in root window coordinates. This can be
+    /// used to check whether a window has moved to another monitor.
+    pub fn get_monitor_index(&self, position: PhyPoint) -> Option<usize> {
+        self.with_cached_monitors(|monitors| {
+            monitors.iter().position(|monitor| monitor.contains(position))
+        })
+    }
+
+    fn with_cached_monitors<T>(&self, f: impl FnOnce(&[RandrMonitor]) -> T) -> T {
+        let mut cache = self.scaling_cache.borrow_mut();
+        f(cache.monitors.get_or_insert_with(|| self.get_randr_monitors()))
+    }
+
+    /// Forget the cached `Xft.dpi` setting and monitor configuration. This should be called when
+    /// the root window's `RESOURCE_MANAGER` property or the RandR configuration changes.
+    pub fn invalidate_scaling_cache(&self) {
+        *self.scaling_cache.borrow_mut() = ScalingCache::default();
+    }
+
+    // Only use this function as a fallback, the physical size of the screen is often made up.
     fn get_scaling_screen_dimensions(&self) -> Option<f64> {
         // Figure out screen information
         let setup = self.conn.get_setup();
@@ -123,6 +309,10 @@ impl XcbConnection {
         let width_mm = screen.width_in_millimeters() as f64;
         let height_px = screen.height_in_pixels() as f64;
         let height_mm = screen.height_in_millimeters() as f64;
+        if height_mm == 0.0 {
+            return None;
+        }
+
         let _xres = width_px * 25.4 / width_mm;
         let yres = height_px * 25.4 / height_mm;
 
@@ -132,9 +322,60 @@ impl XcbConnection {
         Some(yscale)
     }
 
-    #[inline]
-    pub fn get_scaling(&self) -> Option<f64> {
-        self.get_scaling_xft().or_else(|| self.get_scaling_screen_dimensions())
+    /// The system scale factor for a window whose center is at `position`, in root window
+    /// coordinates, taken from the first of `sources` that has a value. Also returns which source
+    /// that was.
+    pub fn get_scaling(
+        &self, sources: &[ScaleSource], position: PhyPoint,
+    ) -> Option<(f64, ScaleSource)> {
+        sources.iter().find_map(|&source| {
+            let scaling = match source {
+                ScaleSource::GdkScale => get_scaling_env("GDK_SCALE"),
+                ScaleSource::QtScaleFactor => get_scaling_env("QT_SCALE_FACTOR"),
+                ScaleSource::XftDpi => self.get_scaling_xft(),
+                ScaleSource::MonitorDpi => self.get_scaling_monitor(position),
+                ScaleSource::ScreenDpi => self.get_scaling_screen_dimensions(),
+                ScaleSource::System => None,
+            };
+
+            scaling.map(|scaling| (scaling, source))
+        })
+    }
+
+    /// Have the X server send `PropertyNotify` events for the root window and RandR screen change
+    /// events to this connection, so changes to the system scale factor can be picked up. Returns
+    /// the RandR event base, or `None` if RandR is not available.
+    pub fn watch_scaling_changes(&self) -> Option<u8> {
+        let root = self.root_window();
+
+        // `Xft.dpi` is stored in the root window's `RESOURCE_MANAGER` property
+        xcb::change_window_attributes(
+            &self.conn,
+            root,
+            &[(xcb::CW_EVENT_MASK, xcb::EVENT_MASK_PROPERTY_CHANGE)],
+        );
+
+        let randr = self.conn.get_extension_data(randr::id())?;
+        if !randr.present() {
+            return None;
+        }
+
+        // RandR 1.3 is needed for `GetScreenResourcesCurrent`, and the monitors from 1.5 are used
+        // when they're available
+        let version = randr::query_version(&self.conn, 1, 5).get_reply().ok()?;
+        if (version.major_version(), version.minor_version()) < (1, 3) {
+            return None;
+        }
+
+        randr::select_input(
+            &self.conn,
+            root,
+            (randr::NOTIFY_MASK_SCREEN_CHANGE
+                | randr::NOTIFY_MASK_CRTC_CHANGE
+                | randr::NOTIFY_MASK_OUTPUT_CHANGE) as u16,
+        );
+
+        Some(randr.first_event())
     }
 
     #[inline]
@@ -143,4 +384,352 @@ impl XcbConnection {
 
         *self.cursor_cache.entry(cursor).or_insert_with(|| cursor::get_xcursor(dpy, cursor))
     }
+
+    /// The `_NET_WM_CM_Sn` selection owned by the compositing manager for our screen.
+    pub fn get_compositor_selection(&self) -> Option<u32> {
+        let name = format!("_NET_WM_CM_S{}", self.xlib_display);
+
+        xcb::intern_atom(&self.conn, false, &name).get_reply().map(|reply| reply.atom()).ok()
+    }
+
+    /// Whether some client currently owns `selection`.
+    pub fn selection_has_owner(&self, selection: u32) -> bool {
+        xcb::get_selection_owner(&self.conn, selection)
+            .get_reply()
+            .is_ok_and(|reply| reply.owner() != xcb::NONE)
+    }
+
+    /// Have the X server send XFixes `SelectionNotify` events to `window` whenever the owner of
+    /// `selection` changes. Returns the XFixes event base, or `None` if XFixes is not available.
+    pub fn watch_selection_owner(&self, window: u32, selection: u32) -> Option<u8> {
+        let xfixes = self.conn.get_extension_data(xcb::xfixes::id())?;
+        if !xfixes.present() {
+            return None;
+        }
+
+        // XFixes requires the client to announce its supported version before using it
+        xcb::xfixes::query_version(&self.conn, 5, 0).get_reply().ok()?;
+        xcb::xfixes::select_selection_input(
+            &self.conn,
+            window,
+            selection,
+            xcb::xfixes::SELECTION_EVENT_MASK_SET_SELECTION_OWNER
+                | xcb::xfixes::SELECTION_EVENT_MASK_SELECTION_WINDOW_DESTROY
+                | xcb::xfixes::SELECTION_EVENT_MASK_SELECTION_CLIENT_CLOSE,
+        );
+
+        Some(xfixes.first_event())
+    }
+
+    /// All active monitors, see [crate::monitors].
+    pub fn get_monitors(&self) -> Vec<MonitorInfo> {
+        let scaling_xft = self.read_scaling_xft();
+        let work_area = self.get_work_area();
+        let resources =
+            randr::get_screen_resources_current(&self.conn, self.root_window()).get_reply().ok();
+
+        let monitors = self.get_randr_monitors();
+        if monitors.is_empty() {
+            // Without RandR 1.5 we'll treat the entire screen as a single monitor
+            let setup = self.conn.get_setup();
+            let screen = setup.roots().nth(self.xlib_display as usize).unwrap();
+            let position = PhyPoint::new(0, 0);
+            let size = PhySize::new(
+                u32::from(screen.width_in_pixels()),
+                u32::from(screen.height_in_pixels()),
+            );
+            let (work_area_position, work_area_size) = work_area.unwrap_or((position, size));
+
+            return vec![MonitorInfo {
+                name: String::new(),
+                position,
+                size,
+                work_area_position,
+                work_area_size,
+                scale: scaling_xft.or_else(|| self.get_scaling_screen_dimensions()).unwrap_or(1.0),
+                refresh_rate: None,
+                is_primary: true,
+            }];
+        }
+
+        monitors
+            .iter()
+            .map(|monitor| {
+                let name = xcb::get_atom_name(&self.conn, monitor.name)
+                    .get_reply()
+                    .map(|reply| reply.name().to_owned())
+                    .unwrap_or_default();
+                let position = PhyPoint::new(monitor.x, monitor.y);
+                let size = PhySize::new(monitor.width, monitor.height);
+
+                // `_NET_WORKAREA` only describes a single area spanning all monitors, so the best we
+                // can do is to clip that to the monitor's bounds
+                let (work_area_position, work_area_size) = work_area
+                    .and_then(|(work_area_position, work_area_size)| {
+                        intersect(position, size, work_area_position, work_area_size)
+                    })
+                    .unwrap_or((position, size));
+
+                let refresh_rate = resources.as_ref().and_then(|resources| {
+                    monitor
+                        .outputs
+                        .first()
+                        .and_then(|&output| self.get_refresh_rate(resources, output))
+                });
+
+                MonitorInfo {
+                    name,
+                    position,
+                    size,
+                    work_area_position,
+                    work_area_size,
+                    scale: scaling_xft
+                        .or_else(|| monitor.get_scaling())
+                        .or_else(|| self.get_scaling_screen_dimensions())
+                        .unwrap_or(1.0),
+                    refresh_rate,
+                    is_primary: monitor.primary,
+                }
+            })
+            .collect()
+    }
+
+    /// The monitors as reported by RandR 1.5. Returns an empty list if the X server doesn't
+    /// support RandR 1.5.
+    fn get_randr_monitors(&self) -> Vec<RandrMonitor> {
+        use x11::xrandr::{XRRFreeMonitors, XRRGetMonitors};
+
+        if !self.conn.get_extension_data(randr::id()).is_some_and(|data| data.present()) {
+            return Vec::new();
+        }
+
+        // Xlib's default error handler exits the process, so we can't just try calling
+        // `XRRGetMonitors()` on older X servers
+        let supports_monitors = randr::query_version(&self.conn, 1, 5)
+            .get_reply()
+            .is_ok_and(|version| (version.major_version(), version.minor_version()) >= (1, 5));
+        if !supports_monitors {
+            return Vec::new();
+        }
+
+        let display = self.conn.get_raw_dpy();
+        unsafe {
+            let mut num_monitors = 0;
+            let monitors_ptr =
+                XRRGetMonitors(display, self.root_window().into(), 1, &mut num_monitors);
+            if monitors_ptr.is_null() {
+                return Vec::new();
+            }
+
+            let monitors = std::slice::from_raw_parts(monitors_ptr, num_monitors as usize)
+                .iter()
+                .map(|monitor| RandrMonitor {
+                    name: monitor.name as u32,
+                    primary: monitor.primary != 0,
+                    x: monitor.x,
+                    y: monitor.y,
+                    width: monitor.width as u32,
+                    height: monitor.height as u32,
+                    height_mm: monitor.mheight as u32,
+                    outputs: std::slice::from_raw_parts(monitor.outputs, monitor.noutput as usize)
+                        .iter()
+                        .map(|&output| output as u32)
+                        .collect(),
+                })
+                .collect();
+            XRRFreeMonitors(monitors_ptr);
+
+            monitors
+        }
+    }
+
+    /// The refresh rate of the mode `output` is currently using.
+    fn get_refresh_rate(
+        &self, resources: &randr::GetScreenResourcesCurrentReply, output: u32,
+    ) -> Option<f64> {
+        let timestamp = resources.config_timestamp();
+        let crtc = randr::get_output_info(&self.conn, output, timestamp).get_reply().ok()?.crtc();
+        if crtc == xcb::NONE {
+            return None;
+        }
+
+        let mode = randr::get_crtc_info(&self.conn, crtc, timestamp).get_reply().ok()?.mode();
+        let mode_info = resources.modes().find(|mode_info| mode_info.id() == mode)?;
+
+        let htotal = f64::from(mode_info.htotal());
+        let mut vtotal = f64::from(mode_info.vtotal());
+        if mode_info.mode_flags() & randr::MODE_FLAG_DOUBLE_SCAN != 0 {
+            vtotal *= 2.0;
+        }
+        if mode_info.mode_flags() & randr::MODE_FLAG_INTERLACE != 0 {
+            vtotal /= 2.0;
+        }
+        if htotal == 0.0 || vtotal == 0.0 {
+            return None;
+        }
+
+        Some(f64::from(mode_info.dot_clock()) / (htotal * vtotal))
+    }
+
+    /// The window manager's `_NET_WORKAREA` for the current desktop.
+    fn get_work_area(&self) -> Option<(PhyPoint, PhySize)> {
+        let net_workarea = self.atoms.net_workarea?;
+        let root = self.root_window();
+
+        let current_desktop = self
+            .atoms
+            .net_current_desktop
+            .and_then(|net_current_desktop| {
+                xcb::get_property(
+                    &self.conn,
+                    false,
+                    root,
+                    net_current_desktop,
+                    xcb::ATOM_CARDINAL,
+                    0,
+                    1,
+                )
+                .get_reply()
+                .ok()
+            })
+            .and_then(|reply| reply.value::<u32>().first().copied())
+            .unwrap_or(0);
+
+        // The property contains an `x, y, width, height` tuple for every desktop
+        let reply = xcb::get_property(
+            &self.conn,
+            false,
+            root,
+            net_workarea,
+            xcb::ATOM_CARDINAL,
+            current_desktop * 4,
+            4,
+        )
+        .get_reply()
+        .ok()?;
+        match *reply.value::<u32>() {
+            [x, y, width, height] => {
+                Some((PhyPoint::new(x as i32, y as i32), PhySize::new(width, height)))
+            }
+            _ => None,
+        }
+    }
+}
+
+/// Read a scale factor from an environment variable, ignoring values that don't make sense.
+fn get_scaling_env(name: &str) -> Option<f64> {
+    let scaling: f64 = std::env::var(name).ok()?.trim().parse().ok()?;
+
+    (scaling.is_finite() && scaling > 0.0).then_some(scaling)
+}
+
+/// A monitor as reported by RandR 1.5. A single monitor can span multiple outputs, for instance
+/// for tiled displays.
+struct RandrMonitor {
+    name: u32,
+    primary: bool,
+    x: i32,
+    y: i32,
+    width: u32,
+    height: u32,
+    height_mm: u32,
+    outputs: Vec<u32>,
+}
+
+impl RandrMonitor {
+    fn contains(&self, position: PhyPoint) -> bool {
+        let (x, y) = (i64::from(position.x), i64::from(position.y));
+
+        x >= i64::from(self.x)
+            && y >= i64::from(self.y)
+            && x < i64::from(self.x) + i64::from(self.width)
+            && y < i64::from(self.y) + i64::from(self.height)
+    }
+
+    /// The scale factor derived from the monitor's physical size, see
+    /// `XcbConnection::get_scaling_screen_dimensions` for the derivation.
+    fn get_scaling(&self) -> Option<f64> {
+        // Projectors and some virtual machines don't report a physical size
+        if self.height_mm == 0 {
+            return None;
+        }
+
+        let yres = f64::from(self.height) * 25.4 / f64::from(self.height_mm);
+
+        Some(yres / 96.0)
+    }
+}
+
+/// The intersection of two rectangles, or `None` if they don't overlap.
+fn intersect(
+    a_position: PhyPoint, a_size: PhySize, b_position: PhyPoint, b_size: PhySize,
+) -> Option<(PhyPoint, PhySize)> {
+    let left = a_position.x.max(b_position.x);
+    let top = a_position.y.max(b_position.y);
+    let right = (i64::from(a_position.x) + i64::from(a_size.width))
+        .min(i64::from(b_position.x) + i64::from(b_size.width));
+    let bottom = (i64::from(a_position.y) + i64::from(a_size.height))
+        .min(i64::from(b_position.y) + i64::from(b_size.height));
+
+    if i64::from(left) < right && i64::from(top) < bottom {
+        Some((
+            PhyPoint::new(left, top),
+            PhySize::new((right - i64::from(left)) as u32, (bottom - i64::from(top)) as u32),
+        ))
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn intersect_overlapping_rectangles() {
+        let intersection = intersect(
+            PhyPoint::new(0, 0),
+            PhySize::new(100, 100),
+            PhyPoint::new(50, -20),
+            PhySize::new(100, 100),
+        );
+
+        assert_eq!(intersection, Some((PhyPoint::new(50, 0), PhySize::new(50, 80))));
+    }
+
+    #[test]
+    fn intersect_contained_rectangle() {
+        let intersection = intersect(
+            PhyPoint::new(1920, 0),
+            PhySize::new(2560, 1440),
+            PhyPoint::new(2000, 100),
+            PhySize::new(800, 600),
+        );
+
+        assert_eq!(intersection, Some((PhyPoint::new(2000, 100), PhySize::new(800, 600))));
+    }
+
+    #[test]
+    fn intersect_touching_rectangles() {
+        let intersection = intersect(
+            PhyPoint::new(0, 0),
+            PhySize::new(1920, 1080),
+            PhyPoint::new(1920, 0),
+            PhySize::new(1920, 1080),
+        );
+
+        assert_eq!(intersection, None);
+    }
+
+    #[test]
+    fn intersect_disjoint_rectangles() {
+        let intersection = intersect(
+            PhyPoint::new(0, 0),
+            PhySize::new(10, 10),
+            PhyPoint::new(-100, -100),
+            PhySize::new(10, 10),
+        );
+
+        assert_eq!(intersection, None);
+    }
 }
@@ -0,0 +1,73 @@
+//! The client side of the XEmbed protocol, which hosts use to manage the keyboard focus of
+//! embedded windows: https://specifications.freedesktop.org/xembed-spec/xembed-spec-latest.html
+
+use super::XcbConnection;
+
+/// The version of the protocol we implement.
+const XEMBED_VERSION: u32 = 0;
+
+/// Flag for `_XEMBED_INFO` indicating that the embedder should map the client window.
+const XEMBED_MAPPED: u32 = 1 << 0;
+
+// Messages sent as `_XEMBED` client messages. The second data field contains the message type.
+/// Sent by the embedder once the client has been embedded. The fourth data field contains the
+/// embedder's window.
+pub const XEMBED_EMBEDDED_NOTIFY: u32 = 0;
+pub const XEMBED_WINDOW_ACTIVATE: u32 = 1;
+pub const XEMBED_WINDOW_DEACTIVATE: u32 = 2;
+/// Sent by the client to ask the embedder for the focus.
+pub const XEMBED_REQUEST_FOCUS: u32 = 3;
+pub const XEMBED_FOCUS_IN: u32 = 4;
+pub const XEMBED_FOCUS_OUT: u32 = 5;
+
+/// What the embedder has told us so far.
+pub(super) struct XEmbedState {
+    /// The embedder's window. This is `None` until we receive `XEMBED_EMBEDDED_NOTIFY`, which
+    /// won't happen if the host doesn't implement XEmbed.
+    pub embedder: Option<u32>,
+    /// Whether the embedder's top level window is active. The embedder should send
+    /// `XEMBED_WINDOW_ACTIVATE` right after embedding us, but not every host does so this starts
+    /// out as true.
+    pub window_active: bool,
+    /// Whether the embedder has moved the focus within its top level window to us.
+    pub focused: bool,
+}
+
+impl XEmbedState {
+    pub fn new() -> Self {
+        Self { embedder: None, window_active: true, focused: false }
+    }
+
+    /// Whether the window has keyboard focus according to the embedder.
+    pub fn has_focus(&self) -> bool {
+        self.window_active && self.focused
+    }
+}
+
+/// Advertise XEmbed support on `window` by setting its `_XEMBED_INFO` property.
+pub fn set_xembed_info(xcb_connection: &XcbConnection, window: u32) {
+    if let Some(xembed_info) = xcb_connection.atoms.xembed_info {
+        xcb::change_property(
+            &xcb_connection.conn,
+            xcb::PROP_MODE_REPLACE as u8,
+            window,
+            xembed_info,
+            xembed_info,
+            32,
+            &[XEMBED_VERSION, XEMBED_MAPPED],
+        );
+    }
+}
+
+/// Send an XEmbed message to the embedder.
+pub fn send_message(xcb_connection: &XcbConnection, embedder: u32, message: u32) {
+    let xembed = match xcb_connection.atoms.xembed {
+        Some(xembed) => xembed,
+        None => return,
+    };
+
+    let data = xcb::ClientMessageData::from_data32([xcb::CURRENT_TIME, message, 0, 0, 0]);
+    let event = xcb::ClientMessageEvent::new(32, embedder, xembed, data);
+    xcb::send_event(&xcb_connection.conn, false, embedder, xcb::EVENT_MASK_NO_EVENT, &event);
+    xcb_connection.conn.flush();
+}
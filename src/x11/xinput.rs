@@ -0,0 +1,576 @@
+//! Support for the XInput 2 extension: https://www.x.org/releases/X11R7.7/doc/inputproto/XI2proto.txt
+//!
+//! The XInput bindings in xcb 0.9 don't compile, so requests are sent through Xlib and the events
+//! are decoded by hand from the `GenericEvent`s xcb hands us.
+
+use std::os::raw::{c_int, c_ulong};
+
+use x11::xinput;
+use x11::xinput2;
+use x11::xlib;
+
+use super::XcbConnection;
+use crate::PhyPoint;
+
+/// The XInput version we ask for. 2.2 is the first version with touch events.
+const XI_MAJOR_VERSION: c_int = 2;
+const XI_MINOR_VERSION: c_int = 2;
+
+/// The size of the fixed part of an `XIDeviceEvent`, up to the button mask.
+const DEVICE_EVENT_HEADER_SIZE: usize = 84;
+
+/// The size of the fixed part of an XInput event. xcb inserts a `full_sequence` field after the
+/// first 32 bytes of the event, so the variable length data starts after that.
+const EVENT_HEADER_SIZE: usize = 36;
+
+pub(super) struct XInput {
+    /// The extension's major opcode, used to tell XInput events apart from other extensions'
+    /// generic events.
+    opcode: u8,
+    minor_version: c_int,
+    labels: ValuatorLabels,
+    device_types: DeviceTypes,
+}
+
+/// The atoms the X input drivers use to label valuators. These are `None` if no device has used
+/// the label yet.
+struct ValuatorLabels {
+    /// Used for the pressure of touch screens.
+    mt_pressure: Option<u32>,
+    /// Used for the pressure of pens, and of some single touch screens.
+    pressure: Option<u32>,
+    tilt_x: Option<u32>,
+    tilt_y: Option<u32>,
+    /// Used for the rotation of pens that support it.
+    wheel: Option<u32>,
+}
+
+/// The atoms the X input drivers use for the types of input devices, as listed by
+/// `XListInputDevices()`. These are `None` if no device has used the type yet.
+struct DeviceTypes {
+    touchpad: Option<u32>,
+    /// The generic type for graphics tablets.
+    tablet: Option<u32>,
+    /// Used by the Wacom driver for pens.
+    stylus: Option<u32>,
+    /// Used by the Wacom driver for the eraser end of pens.
+    eraser: Option<u32>,
+}
+
+/// The valuators we use from the X server's input devices.
+pub(super) struct DeviceValuators {
+    /// The scroll valuators of the master pointer devices.
+    pub scroll: Vec<ScrollValuator>,
+    /// The pressure valuators of the slave devices, for touch screens.
+    pub pressure: Vec<AxisValuator>,
+    /// The slave devices that are pens or erasers on a graphics tablet.
+    pub pens: Vec<PenDevice>,
+    /// The slave devices that are touchpads. Their scroll valuators have pixel precision.
+    pub touchpads: Vec<u16>,
+}
+
+impl DeviceValuators {
+    pub fn new() -> Self {
+        Self { scroll: Vec::new(), pressure: Vec::new(), pens: Vec::new(), touchpads: Vec::new() }
+    }
+}
+
+impl XInput {
+    /// Returns `None` if the X server does not support XInput 2.
+    pub fn new(conn: &xcb::Connection) -> Option<Self> {
+        let reply = xcb::query_extension(conn, "XInputExtension").get_reply().ok()?;
+        if !reply.present() {
+            return None;
+        }
+
+        // The X server won't send us any XInput 2 events until we've told it which version we
+        // support
+        let mut major_version = XI_MAJOR_VERSION;
+        let mut minor_version = XI_MINOR_VERSION;
+        let status = unsafe {
+            xinput2::XIQueryVersion(conn.get_raw_dpy(), &mut major_version, &mut minor_version)
+        };
+        if status != xlib::Success as c_int || major_version < XI_MAJOR_VERSION {
+            return None;
+        }
+
+        // These are the labels from `xserver-properties.h`, followed by the device types from
+        // `XI.h`
+        let label_cookies: Vec<_> = [
+            "Abs MT Pressure",
+            "Abs Pressure",
+            "Abs Tilt X",
+            "Abs Tilt Y",
+            "Abs Wheel",
+            "TOUCHPAD",
+            "TABLET",
+            "STYLUS",
+            "ERASER",
+        ]
+        .iter()
+        .map(|name| xcb::intern_atom(conn, true, name))
+        .collect();
+        let label_atoms: Vec<_> = label_cookies
+            .into_iter()
+            .map(|cookie| {
+                cookie
+                    .get_reply()
+                    .ok()
+                    .map(|reply| reply.atom())
+                    .filter(|&atom| atom != xcb::ATOM_NONE)
+            })
+            .collect();
+        let labels = ValuatorLabels {
+            mt_pressure: label_atoms[0],
+            pressure: label_atoms[1],
+            tilt_x: label_atoms[2],
+            tilt_y: label_atoms[3],
+            wheel: label_atoms[4],
+        };
+        let device_types = DeviceTypes {
+            touchpad: label_atoms[5],
+            tablet: label_atoms[6],
+            stylus: label_atoms[7],
+            eraser: label_atoms[8],
+        };
+
+        Some(Self { opcode: reply.major_opcode(), minor_version, labels, device_types })
+    }
+
+    /// Whether the X server supports smooth scrolling through scroll valuators, which was added in
+    /// XInput 2.1.
+    pub fn supports_smooth_scrolling(&self) -> bool {
+        self.minor_version >= 1
+    }
+
+    /// Whether the X server supports touch events, which were added in XInput 2.2.
+    pub fn supports_touch(&self) -> bool {
+        self.minor_version >= 2
+    }
+
+    /// Query the valuators of all input devices.
+    pub fn query_valuators(&self, xcb_connection: &XcbConnection) -> DeviceValuators {
+        let mut valuators = DeviceValuators::new();
+        let device_types = query_device_types(xcb_connection);
+
+        unsafe {
+            let mut num_devices = 0;
+            let devices = xinput2::XIQueryDevice(
+                xcb_connection.conn.get_raw_dpy(),
+                xinput2::XIAllDevices,
+                &mut num_devices,
+            );
+            if devices.is_null() {
+                return valuators;
+            }
+
+            for device in std::slice::from_raw_parts(devices, num_devices as usize) {
+                let is_master = device._use == xinput2::XIMasterPointer;
+                let is_slave = device._use == xinput2::XISlavePointer
+                    || device._use == xinput2::XIFloatingSlave;
+                let device_type = device_types
+                    .iter()
+                    .find(|(deviceid, _)| *deviceid == device.deviceid as u16)
+                    .map(|&(_, device_type)| device_type);
+
+                let has_type =
+                    |expected: Option<u32>| device_type.is_some() && device_type == expected;
+
+                if is_slave && has_type(self.device_types.touchpad) {
+                    valuators.touchpads.push(device.deviceid as u16);
+                }
+
+                let mut mt_pressure = None;
+                let mut pressure = None;
+                let mut tilt_x = None;
+                let mut tilt_y = None;
+                let mut wheel = None;
+                for &class in
+                    std::slice::from_raw_parts(device.classes, device.num_classes as usize)
+                {
+                    match (*class)._type {
+                        xinput2::XIScrollClass if is_master => {
+                            let class = &*(class as *const xinput2::XIScrollClassInfo);
+                            valuators.scroll.push(ScrollValuator {
+                                deviceid: device.deviceid as u16,
+                                number: class.number as usize,
+                                horizontal: class.scroll_type == xinput2::XIScrollTypeHorizontal,
+                                increment: class.increment,
+                                last_value: None,
+                            });
+                        }
+                        xinput2::XIValuatorClass if is_slave => {
+                            let class = &*(class as *const xinput2::XIValuatorClassInfo);
+                            let axis = Some(AxisValuator {
+                                sourceid: device.deviceid as u16,
+                                number: class.number as usize,
+                                min: class.min,
+                                max: class.max,
+                            });
+
+                            let label = Some(class.label as u32);
+                            if label == self.labels.mt_pressure {
+                                mt_pressure = axis;
+                            } else if label == self.labels.pressure {
+                                pressure = axis;
+                            } else if label == self.labels.tilt_x {
+                                tilt_x = axis;
+                            } else if label == self.labels.tilt_y {
+                                tilt_y = axis;
+                            } else if label == self.labels.wheel {
+                                wheel = axis;
+                            }
+                        }
+                        _ => (),
+                    }
+                }
+
+                if let Some(pressure) = mt_pressure.or(pressure) {
+                    valuators.pressure.push(pressure);
+                }
+
+                // Touch screens and some touchpads may also have a regular pressure axis, so a pen
+                // either needs to have tilt axes or be marked as a tablet device. The Wacom driver
+                // creates a separate device for the eraser.
+                let is_eraser = has_type(self.device_types.eraser);
+                let is_pen = tilt_x.is_some()
+                    || tilt_y.is_some()
+                    || has_type(self.device_types.tablet)
+                    || has_type(self.device_types.stylus)
+                    || is_eraser;
+                if let (None, Some(pressure)) = (mt_pressure, pressure.filter(|_| is_pen)) {
+                    valuators.pens.push(PenDevice {
+                        sourceid: device.deviceid as u16,
+                        is_eraser,
+                        pressure_axis: pressure,
+                        tilt_x_axis: tilt_x,
+                        tilt_y_axis: tilt_y,
+                        rotation_axis: wheel,
+                        pressure: 0.0,
+                        tilt_x: 0.0,
+                        tilt_y: 0.0,
+                        rotation: None,
+                    });
+                }
+            }
+
+            xinput2::XIFreeDeviceInfo(devices);
+        }
+
+        valuators
+    }
+
+    /// Select the XInput events in `events`, a combination of the `XI_*Mask` constants, for all
+    /// master devices on `window`. This replaces the previous selection.
+    pub fn select_events(&self, xcb_connection: &XcbConnection, window: u32, events: i32) {
+        let mut mask = events.to_le_bytes();
+        let mut event_mask = xinput2::XIEventMask {
+            deviceid: xinput2::XIAllMasterDevices,
+            mask_len: mask.len() as c_int,
+            mask: mask.as_mut_ptr(),
+        };
+
+        unsafe {
+            let display = xcb_connection.conn.get_raw_dpy();
+            xinput2::XISelectEvents(display, window as c_ulong, &mut event_mask, 1);
+            xlib::XFlush(display);
+        }
+    }
+
+    /// If `event` is an XInput event, returns its type as one of the `XI_*` constants and the
+    /// event's data.
+    pub fn decode_event<'a>(&self, event: &'a xcb::GenericEvent) -> Option<(i32, &'a [u8])> {
+        if event.response_type() & !0x80 != xcb::GE_GENERIC {
+            return None;
+        }
+
+        let data = unsafe {
+            let header = std::slice::from_raw_parts(event.ptr as *const u8, EVENT_HEADER_SIZE);
+            if header[1] != self.opcode {
+                return None;
+            }
+
+            let length = read_u32(header, 4) as usize * 4;
+            std::slice::from_raw_parts(event.ptr as *const u8, EVENT_HEADER_SIZE + length)
+        };
+
+        Some((read_u16(data, 8) as i32, data))
+    }
+}
+
+/// The IDs and type atoms of all input devices. XInput 2 doesn't expose device types, so this uses
+/// the XInput 1 device list.
+fn query_device_types(xcb_connection: &XcbConnection) -> Vec<(u16, u32)> {
+    unsafe {
+        let mut num_devices = 0;
+        let devices =
+            xinput::XListInputDevices(xcb_connection.conn.get_raw_dpy(), &mut num_devices);
+        if devices.is_null() {
+            return Vec::new();
+        }
+
+        let device_types = std::slice::from_raw_parts(devices, num_devices as usize)
+            .iter()
+            .map(|device| (device.id as u16, device.type_ as u32))
+            .collect();
+        xinput::XFreeDeviceList(devices);
+
+        device_types
+    }
+}
+
+/// A scroll valuator on a master pointer device, used for smooth scrolling.
+pub(super) struct ScrollValuator {
+    pub deviceid: u16,
+    /// The valuator's index in the device events.
+    pub number: usize,
+    pub horizontal: bool,
+    /// The change in the valuator's value that corresponds to a single scroll wheel click.
+    pub increment: f64,
+    /// The valuator's last known value. The valuator is an absolute axis, so its first value after
+    /// the pointer enters the window or the device changes can't be used to compute a delta.
+    pub last_value: Option<f64>,
+}
+
+/// An absolute axis on a slave device, like a touch screen's pressure.
+#[derive(Clone, Copy)]
+pub(super) struct AxisValuator {
+    /// The slave device, which is the source of the master device's events.
+    pub sourceid: u16,
+    /// The valuator's index in the device events.
+    pub number: usize,
+    pub min: f64,
+    pub max: f64,
+}
+
+impl AxisValuator {
+    /// Map `value` to the `[0, 1]` range.
+    pub fn normalize(&self, value: f64) -> f64 {
+        if self.max > self.min {
+            ((value - self.min) / (self.max - self.min)).clamp(0.0, 1.0)
+        } else {
+            0.0
+        }
+    }
+
+    /// Map `value` to the `[-1, 1]` range.
+    pub fn normalize_signed(&self, value: f64) -> f64 {
+        self.normalize(value) * 2.0 - 1.0
+    }
+}
+
+/// A pen or eraser on a graphics tablet, and its last known state. Motion events only include the
+/// valuators that changed.
+pub(super) struct PenDevice {
+    pub sourceid: u16,
+    pub is_eraser: bool,
+    pressure_axis: AxisValuator,
+    tilt_x_axis: Option<AxisValuator>,
+    tilt_y_axis: Option<AxisValuator>,
+    rotation_axis: Option<AxisValuator>,
+    /// In the `[0, 1]` range.
+    pub pressure: f64,
+    /// In the `[-1, 1]` range.
+    pub tilt_x: f64,
+    pub tilt_y: f64,
+    /// In the `[0, 1]` range, or `None` if the pen doesn't support rotation.
+    pub rotation: Option<f64>,
+}
+
+impl PenDevice {
+    /// Update the pen's state with the valuators included in `event`.
+    pub fn update(&mut self, event: &DeviceEvent) {
+        if let Some(value) = event.valuator(self.pressure_axis.number) {
+            self.pressure = self.pressure_axis.normalize(value);
+        }
+        if let Some(axis) = &self.tilt_x_axis {
+            if let Some(value) = event.valuator(axis.number) {
+                self.tilt_x = axis.normalize_signed(value);
+            }
+        }
+        if let Some(axis) = &self.tilt_y_axis {
+            if let Some(value) = event.valuator(axis.number) {
+                self.tilt_y = axis.normalize_signed(value);
+            }
+        }
+        if let Some(axis) = &self.rotation_axis {
+            if let Some(value) = event.valuator(axis.number) {
+                self.rotation = Some(axis.normalize(value));
+            }
+        }
+    }
+}
+
+/// The fields of an `XIDeviceEvent` we care about, for `XI_Motion` and `XI_Touch*` events.
+pub(super) struct DeviceEvent<'a> {
+    pub deviceid: u16,
+    /// The slave device that generated the event.
+    pub sourceid: u16,
+    /// The X server time the event was generated at.
+    pub time: u32,
+    /// The touch ID for touch events.
+    pub detail: u32,
+    /// The position in physical pixels relative to the window, with sub-pixel precision.
+    pub x: f64,
+    pub y: f64,
+    /// A combination of the `XI*` event flags.
+    pub flags: u32,
+    /// The effective modifiers, in the same format as a core event's state.
+    pub modifiers: u16,
+    button_mask: &'a [u8],
+    valuator_mask: &'a [u8],
+    valuator_values: &'a [u8],
+}
+
+impl DeviceEvent<'_> {
+    pub fn parse(data: &[u8]) -> DeviceEvent<'_> {
+        let buttons_len = read_u16(data, 52) as usize * 4;
+        let valuators_len = read_u16(data, 54) as usize * 4;
+        let valuator_mask_offset = DEVICE_EVENT_HEADER_SIZE + buttons_len;
+        let valuator_values_offset = valuator_mask_offset + valuators_len;
+
+        DeviceEvent {
+            deviceid: read_u16(data, 10),
+            sourceid: read_u16(data, 56),
+            time: read_u32(data, 12),
+            detail: read_u32(data, 16),
+            x: read_fp1616(data, 44),
+            y: read_fp1616(data, 48),
+            flags: read_u32(data, 60),
+            modifiers: read_u32(data, 76) as u16,
+            button_mask: &data[DEVICE_EVENT_HEADER_SIZE..valuator_mask_offset],
+            valuator_mask: &data[valuator_mask_offset..valuator_values_offset],
+            valuator_values: &data[valuator_values_offset..],
+        }
+    }
+
+    /// The position in whole physical pixels relative to the window.
+    pub fn position(&self) -> PhyPoint {
+        PhyPoint::new(self.x.floor() as i32, self.y.floor() as i32)
+    }
+
+    /// Whether `button` was held down when the event was generated. Buttons are numbered like the
+    /// core pointer buttons, starting at 1.
+    pub fn button_pressed(&self, button: usize) -> bool {
+        self.button_mask.get(button / 8).is_some_and(|byte| byte & (1 << (button % 8)) != 0)
+    }
+
+    /// The value of valuator `number`, if it is included in this event.
+    pub fn valuator(&self, number: usize) -> Option<f64> {
+        let index = valuator_index(self.valuator_mask, number)?;
+
+        Some(read_fp3232(self.valuator_values, index * 8))
+    }
+}
+
+/// Returns the x and y values from an `XI_RawMotion` event's data, without pointer acceleration
+/// applied.
+pub fn raw_motion_delta(data: &[u8]) -> (f64, f64) {
+    let mask_len = read_u16(data, 22) as usize * 4;
+    let mask = &data[EVENT_HEADER_SIZE..EVENT_HEADER_SIZE + mask_len];
+    let num_values: u32 = mask.iter().map(|byte| byte.count_ones()).sum();
+
+    // The event contains the accelerated values for all valuators set in the mask, followed by
+    // the raw values. Valuators 0 and 1 are the x and y axes.
+    let raw_values_offset = EVENT_HEADER_SIZE + mask_len + num_values as usize * 8;
+    let raw_value = |number| {
+        valuator_index(mask, number)
+            .map_or(0.0, |index| read_fp3232(data, raw_values_offset + index * 8))
+    };
+
+    (raw_value(0), raw_value(1))
+}
+
+/// The values in an event only include the valuators that are set in the event's valuator mask.
+/// Returns the index of valuator `number`'s value, or `None` if it's not set.
+fn valuator_index(mask: &[u8], number: usize) -> Option<usize> {
+    let byte = *mask.get(number / 8)?;
+    if byte & (1 << (number % 8)) == 0 {
+        return None;
+    }
+
+    let preceding_bytes: u32 = mask[..number / 8].iter().map(|byte| byte.count_ones()).sum();
+    let preceding_bits = (byte & ((1 << (number % 8)) - 1)).count_ones();
+
+    Some((preceding_bytes + preceding_bits) as usize)
+}
+
+fn read_u16(data: &[u8], offset: usize) -> u16 {
+    u16::from_ne_bytes([data[offset], data[offset + 1]])
+}
+
+fn read_u32(data: &[u8], offset: usize) -> u32 {
+    u32::from_ne_bytes([data[offset], data[offset + 1], data[offset + 2], data[offset + 3]])
+}
+
+/// Read a 16.16 fixed point number.
+fn read_fp1616(data: &[u8], offset: usize) -> f64 {
+    read_u32(data, offset) as i32 as f64 / 65536.0
+}
+
+/// Read a 32.32 fixed point number.
+fn read_fp3232(data: &[u8], offset: usize) -> f64 {
+    let integral = read_u32(data, offset) as i32;
+    let fraction = read_u32(data, offset + 4);
+
+    integral as f64 + fraction as f64 / (1u64 << 32) as f64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Encode a value as a 32.32 fixed point number.
+    fn fp3232(integral: i32, fraction: u32) -> Vec<u8> {
+        let mut data = integral.to_ne_bytes().to_vec();
+        data.extend_from_slice(&fraction.to_ne_bytes());
+
+        data
+    }
+
+    #[test]
+    fn read_fp3232_whole_numbers() {
+        assert_eq!(read_fp3232(&fp3232(42, 0), 0), 42.0);
+        assert_eq!(read_fp3232(&fp3232(-3, 0), 0), -3.0);
+    }
+
+    #[test]
+    fn read_fp3232_fractions() {
+        assert_eq!(read_fp3232(&fp3232(1, 0x4000_0000), 0), 1.25);
+        // The fraction is always positive, so negative values are stored as the next lower integer
+        // plus a fraction
+        assert_eq!(read_fp3232(&fp3232(-2, 0x8000_0000), 0), -1.5);
+    }
+
+    #[test]
+    fn read_fp3232_at_offset() {
+        let mut data = fp3232(7, 0);
+        data.extend(fp3232(-1, 0xC000_0000));
+
+        assert_eq!(read_fp3232(&data, 8), -0.25);
+    }
+
+    #[test]
+    fn valuator_index_skips_unset_valuators() {
+        // Valuators 0, 1, 3, and 9 are set
+        let mask = [0b0000_1011, 0b0000_0010, 0, 0];
+
+        assert_eq!(valuator_index(&mask, 0), Some(0));
+        assert_eq!(valuator_index(&mask, 1), Some(1));
+        assert_eq!(valuator_index(&mask, 2), None);
+        assert_eq!(valuator_index(&mask, 3), Some(2));
+        assert_eq!(valuator_index(&mask, 9), Some(3));
+    }
+
+    #[test]
+    fn valuator_index_outside_mask() {
+        assert_eq!(valuator_index(&[0xFF; 4], 32), None);
+        assert_eq!(valuator_index(&[], 0), None);
+    }
+
+    #[test]
+    fn read_fp1616_values() {
+        assert_eq!(read_fp1616(&0x0001_8000u32.to_ne_bytes(), 0), 1.5);
+        assert_eq!(read_fp1616(&(-0x0001_8000i32).to_ne_bytes(), 0), -1.5);
+    }
+}